@@ -6,4 +6,30 @@ pub const POOL_SEED: &str = "amm_pool";
 #[constant]
 pub const LP_TOKEN_MINT_SEED: &str = "lp_mint";
 
+#[constant]
+pub const AMM_CONFIG_SEED: &str = "amm_config";
+
+// max number of fee tiers the config can hold
+#[constant]
+pub const MAX_FEE_TIERS: usize = 8;
+
+// upper bound for the total swap fee (lp + protocol) in basis points (1000 bp = 10%)
+#[constant]
+pub const MAX_FEE_BP: u16 = 1_000;
+
+// lp tokens minted to the pool owned lp account on the first deposit and never withdrawable
+#[constant]
+pub const MINIMUM_LIQUIDITY: u64 = 1_000;
+
+#[constant]
+pub const OBSERVATIONS_SEED: &str = "observations";
+
+// max observation slots per pool, growing from one slot to all of them stays under the
+// 10KB realloc limit of a single instruction
+#[constant]
+pub const OBSERVATION_CAPACITY: u16 = 200;
+
+// max `seconds_ago` entries per `observe` call, bounded by the 1KB return data limit
+#[constant]
+pub const MAX_OBSERVE_QUERIES: usize = 25;
 
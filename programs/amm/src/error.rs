@@ -12,4 +12,44 @@ pub enum PoolError {
     OverFlowError,
     #[msg("Invalid Amount")]
     InvalidAmount,
+    #[msg("Fee exceeds the maximum allowed")]
+    InvalidFee,
+    #[msg("Signer is not the pending authority")]
+    InvalidPendingAuthority,
+    #[msg("Transaction deadline has passed")]
+    Expired,
+    #[msg("First deposit does not cover the minimum liquidity")]
+    InsufficientInitialLiquidity,
+    #[msg("Pool reserves are zero")]
+    ZeroReserves,
+    #[msg("Division by zero")]
+    DivisionByZero,
+    #[msg("Insufficient liquidity in the pool")]
+    InsufficientLiquidity,
+    #[msg("Output exceeds pool reserves")]
+    OutputExceedsReserves,
+    #[msg("Vault did not receive the expected amount")]
+    TransferAmountMismatch,
+    #[msg("Pool mints must be different")]
+    SameMint,
+    #[msg("Pool mints must be sorted, mint_x < mint_y")]
+    InvalidMintOrder,
+    #[msg("Signer is not allowed to perform this action")]
+    Unauthorized,
+    #[msg("Invalid fee tier")]
+    InvalidFeeTier,
+    #[msg("Protocol fee share cannot exceed 100%")]
+    InvalidProtocolFeeShare,
+    #[msg("AMM is paused")]
+    AmmPaused,
+    #[msg("Mint has a token extension the AMM does not support")]
+    UnsupportedMintExtension,
+    #[msg("Observation window must be positive")]
+    InvalidObservationWindow,
+    #[msg("Observation cardinality must grow and stay within the capacity")]
+    InvalidObservationCardinality,
+    #[msg("Requested time is older than the oldest observation")]
+    ObservationTooOld,
+    #[msg("Too many observation queries")]
+    TooManyObservationQueries,
 }
@@ -0,0 +1,25 @@
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct FeeUpdated {
+    pub pool: Pubkey,
+    pub old_lp_fee_bp: u16,
+    pub old_protocol_fee_bp: u16,
+    pub new_lp_fee_bp: u16,
+    pub new_protocol_fee_bp: u16,
+}
+
+#[event]
+pub struct AuthorityTransferred {
+    pub pool: Pubkey,
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
+#[event]
+pub struct ProtocolFeesCollected {
+    pub pool: Pubkey,
+    pub treasury: Pubkey,
+    pub amount_x: u64,
+    pub amount_y: u64,
+}
@@ -1,5 +1,22 @@
-use crate::error::PoolError;
-use anchor_lang::prelude::*;
+use crate::{
+    error::PoolError, PriceObservation, LP_TOKEN_MINT_SEED, MAX_FEE_BP, MAX_FEE_TIERS, POOL_SEED,
+};
+use anchor_lang::{prelude::*, system_program};
+use anchor_spl::{
+    associated_token::{
+        get_associated_token_address, get_associated_token_address_with_program_id,
+    },
+    token_2022::spl_token_2022::{
+        self,
+        extension::{
+            transfer_fee::TransferFeeConfig, BaseStateWithExtensions, ExtensionType,
+            StateWithExtensions,
+        },
+    },
+    token_interface::{
+        close_account, sync_native, CloseAccount, SyncNative, TokenAccount, TokenInterface,
+    },
+};
 use integer_sqrt::IntegerSquareRoot;
 
 pub fn get_lp_token_amount_init(x_tokens: u64, y_tokens: u64) -> Result<u64> {
@@ -17,21 +34,21 @@ pub fn get_lp_tokens_to_mint(
     max_x_token: u64,
     max_y_token: u64,
 ) -> Result<u64> {
+    require!(vault_x > 0 && vault_y > 0, PoolError::ZeroReserves);
+
     let lp_x = (max_x_token as u128)
         .checked_mul(lp_suppy as u128)
         .ok_or(PoolError::OverFlowError)?
         .checked_div(vault_x as u128)
-        .ok_or(PoolError::OverFlowError)? as u64;
+        .ok_or(PoolError::DivisionByZero)?;
 
     let lp_y = (max_y_token as u128)
         .checked_mul(lp_suppy as u128)
         .ok_or(PoolError::OverFlowError)?
         .checked_div(vault_y as u128)
-        .ok_or(PoolError::OverFlowError)? as u64;
-
-    let lp_tokens = lp_x.min(lp_y);
+        .ok_or(PoolError::DivisionByZero)?;
 
-    Ok(lp_tokens)
+    to_u64(lp_x.min(lp_y))
 }
 
 // getting the X and Y token amount from the lp token amount
@@ -40,13 +57,486 @@ pub fn xy_from_l(lp_suppy: u64, vault_x: u64, vault_y: u64, lp_amount: u64) -> R
         .checked_mul(lp_amount as u128)
         .ok_or(PoolError::OverFlowError)?
         .checked_div(lp_suppy as u128)
-        .ok_or(PoolError::OverFlowError)? as u64;
+        .ok_or(PoolError::DivisionByZero)?;
 
     let required_y = (vault_y as u128)
         .checked_mul(lp_amount as u128)
-        .unwrap()
+        .ok_or(PoolError::OverFlowError)?
         .checked_div(lp_suppy as u128)
-        .unwrap() as u64;
+        .ok_or(PoolError::DivisionByZero)?;
+
+    Ok((to_u64(required_x)?, to_u64(required_y)?))
+}
+
+// fee part of `amount` for a fee in basis points, rounded down
+// (swap amount * fee in basis points / 10_000) //if 30 is bp, then 0.003 which is 0.3%
+pub fn get_fee_amount(amount: u64, fee_bp: u16) -> Result<u64> {
+    let fee = (amount as u128)
+        .checked_mul(fee_bp as u128)
+        .ok_or(PoolError::OverFlowError)?
+        .checked_div(10_000)
+        .ok_or(PoolError::DivisionByZero)?;
+
+    to_u64(fee)
+}
+
+// output of the constant product curve for `amount_in` (fee included), rounded down
+pub fn get_amount_out(
+    amount_in: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+    total_fee_bp: u16,
+) -> Result<u64> {
+    require!(reserve_in > 0 && reserve_out > 0, PoolError::ZeroReserves);
+
+    // amount after fee which we need to calc the amount of out token
+    let amount_in_after_fee = (amount_in as u128)
+        .checked_mul(
+            10_000u128
+                .checked_sub(total_fee_bp as u128)
+                .ok_or(PoolError::OverFlowError)?,
+        )
+        .ok_or(PoolError::OverFlowError)?
+        .checked_div(10_000)
+        .ok_or(PoolError::DivisionByZero)?;
+
+    // constant K = X * Y, in u128 so realistic reserves do not overflow
+    let k = (reserve_in as u128)
+        .checked_mul(reserve_out as u128)
+        .ok_or(PoolError::OverFlowError)?;
+
+    let new_reserve_in = (reserve_in as u128)
+        .checked_add(amount_in_after_fee)
+        .ok_or(PoolError::OverFlowError)?;
+
+    // rounded up so the rounding stays in the pool
+    let new_reserve_out = k
+        .checked_add(new_reserve_in - 1)
+        .ok_or(PoolError::OverFlowError)?
+        .checked_div(new_reserve_in)
+        .ok_or(PoolError::DivisionByZero)?;
+
+    let amount_out = (reserve_out as u128)
+        .checked_sub(new_reserve_out)
+        .ok_or(PoolError::OutputExceedsReserves)?;
+
+    to_u64(amount_out)
+}
+
+// part of a single sided deposit `amount_in` that has to be swapped so the rest
+// matches the pool ratio after the swap, fee included:
+// s = (sqrt(R^2 (2F - f)^2 + 4 F (F - f) A R) - R (2F - f)) / (2 (F - f))
+// computed in the rationalized form s = 2 F A R / (sqrt(R * inner) + R (2F - f)),
+// which has no cancellation, so scaling down large operands only costs a few
+// low order bits instead of the whole result
+pub fn get_single_sided_swap_amount(
+    amount_in: u64,
+    reserve_in: u64,
+    total_fee_bp: u16,
+) -> Result<u64> {
+    require!(reserve_in > 0, PoolError::ZeroReserves);
+
+    let reserve_in = reserve_in as u128;
+    let two_minus_fee = 20_000u128
+        .checked_sub(total_fee_bp as u128)
+        .ok_or(PoolError::OverFlowError)?;
+    let one_minus_fee = 10_000u128
+        .checked_sub(total_fee_bp as u128)
+        .ok_or(PoolError::OverFlowError)?;
+
+    // R (2F - f)^2 + 4 F (F - f) A, the discriminant is R times this
+    let inner = reserve_in
+        .checked_mul(two_minus_fee * two_minus_fee)
+        .ok_or(PoolError::OverFlowError)?
+        .checked_add(
+            (amount_in as u128)
+                .checked_mul(4 * 10_000 * one_minus_fee)
+                .ok_or(PoolError::OverFlowError)?,
+        )
+        .ok_or(PoolError::OverFlowError)?;
+
+    // when R * inner does not fit, inner is shifted down by an even number of bits
+    // and the root shifted back up by half of it. inner has far more bits than the
+    // shift takes away
+    let root = match reserve_in.checked_mul(inner) {
+        Some(discriminant) => discriminant.integer_sqrt(),
+        None => {
+            let shift = (excess_bits(reserve_in, inner) + 1) & !1;
+            reserve_in
+                .checked_mul(inner >> shift)
+                .ok_or(PoolError::OverFlowError)?
+                .integer_sqrt()
+                << (shift / 2)
+        }
+    };
+
+    let denominator = root
+        .checked_add(
+            reserve_in
+                .checked_mul(two_minus_fee)
+                .ok_or(PoolError::OverFlowError)?,
+        )
+        .ok_or(PoolError::OverFlowError)?;
+    let numerator = (amount_in as u128) * 2 * 10_000;
+
+    // same for 2 F A * R, R and the denominator (at least R (2F - f)) are scaled
+    // down together
+    let swap_amount = match numerator.checked_mul(reserve_in) {
+        Some(product) => product / denominator,
+        None => {
+            let shift = excess_bits(numerator, reserve_in);
+            numerator
+                .checked_mul(reserve_in >> shift)
+                .ok_or(PoolError::OverFlowError)?
+                .checked_div(denominator >> shift)
+                .ok_or(PoolError::DivisionByZero)?
+        }
+    };
+
+    to_u64(swap_amount.min(amount_in as u128))
+}
+
+// bits by which a * b can exceed u128
+fn excess_bits(a: u128, b: u128) -> u32 {
+    (256 - a.leading_zeros() - b.leading_zeros()).saturating_sub(128)
+}
+
+// input needed to take exactly `amount_out` from the pool, inverse of the constant product
+// both divisions round up so the pool never gives out more than it gets
+pub fn get_amount_in(
+    amount_out: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+    total_fee_bp: u16,
+) -> Result<u64> {
+    require!(reserve_in > 0 && reserve_out > 0, PoolError::ZeroReserves);
+    require!(amount_out > 0, PoolError::InvalidAmount);
+    require!(amount_out < reserve_out, PoolError::InsufficientLiquidity);
+
+    // amount that has to reach the curve after fees: ceil(x * dy / (y - dy))
+    let numerator = (reserve_in as u128)
+        .checked_mul(amount_out as u128)
+        .ok_or(PoolError::OverFlowError)?;
+    let denominator = (reserve_out as u128)
+        .checked_sub(amount_out as u128)
+        .ok_or(PoolError::OutputExceedsReserves)?;
+    let amount_in_after_fee = numerator
+        .checked_add(denominator - 1)
+        .ok_or(PoolError::OverFlowError)?
+        .checked_div(denominator)
+        .ok_or(PoolError::DivisionByZero)?;
+
+    // gross up for the fee: ceil(net * 10_000 / (10_000 - fee))
+    let fee_denominator = 10_000u128
+        .checked_sub(total_fee_bp as u128)
+        .ok_or(PoolError::OverFlowError)?;
+    let amount_in = amount_in_after_fee
+        .checked_mul(10_000)
+        .ok_or(PoolError::OverFlowError)?
+        .checked_add(fee_denominator - 1)
+        .ok_or(PoolError::OverFlowError)?
+        .checked_div(fee_denominator)
+        .ok_or(PoolError::DivisionByZero)?;
+
+    to_u64(amount_in)
+}
+
+fn to_u64(value: u128) -> Result<u64> {
+    u64::try_from(value).map_err(|_| PoolError::OverFlowError.into())
+}
+
+// total swap fee (lp + protocol) has to stay within MAX_FEE_BP, which is well below 100%
+pub fn validate_fees(lp_fee_bp: u16, protocol_fee_bp: u16) -> Result<()> {
+    let total_fee_bp = (lp_fee_bp as u32)
+        .checked_add(protocol_fee_bp as u32)
+        .ok_or(PoolError::OverFlowError)?;
+
+    require!(total_fee_bp <= MAX_FEE_BP as u32, PoolError::InvalidFee);
+
+    Ok(())
+}
+
+// (lp fee, protocol fee) of a fee tier, the protocol share of the config is carved out of
+// the tier, which stays the total swap fee
+pub fn split_fee_tier(fee_tier: u16, protocol_fee_share_bp: u16) -> Result<(u16, u16)> {
+    let protocol_fee_bp = ((fee_tier as u32 * protocol_fee_share_bp as u32) / 10_000) as u16;
+    let lp_fee_bp = fee_tier - protocol_fee_bp;
+    validate_fees(lp_fee_bp, protocol_fee_bp)?;
+
+    Ok((lp_fee_bp, protocol_fee_bp))
+}
+
+// fee tiers have to be non empty, unique, and each within MAX_FEE_BP
+pub fn validate_fee_tiers(fee_tiers: &[u16], protocol_fee_share_bp: u16) -> Result<()> {
+    require!(
+        !fee_tiers.is_empty() && fee_tiers.len() <= MAX_FEE_TIERS,
+        PoolError::InvalidFeeTier
+    );
+    require!(
+        protocol_fee_share_bp <= 10_000,
+        PoolError::InvalidProtocolFeeShare
+    );
+
+    for (i, fee_tier) in fee_tiers.iter().enumerate() {
+        require!(*fee_tier <= MAX_FEE_BP, PoolError::InvalidFee);
+        require!(
+            !fee_tiers[..i].contains(fee_tier),
+            PoolError::InvalidFeeTier
+        );
+    }
+
+    Ok(())
+}
+
+// numerator / denominator in UQ64.64 fixed point, both are u64 so it can not overflow
+pub fn get_price_uq64_64(numerator: u64, denominator: u64) -> u128 {
+    ((numerator as u128) << 64) / denominator as u128
+}
+
+// time weighted average prices (UQ64.64) between two observations of the same pool,
+// returns (price of x in y, price of y in x)
+pub fn get_twap(start: &PriceObservation, end: &PriceObservation) -> Result<(u128, u128)> {
+    require!(
+        end.timestamp > start.timestamp,
+        PoolError::InvalidObservationWindow
+    );
+
+    let elapsed = (end.timestamp - start.timestamp) as u128;
+
+    // accumulators wrap, the wrapped difference is still correct
+    let price_x = end
+        .price_x_cumulative
+        .wrapping_sub(start.price_x_cumulative)
+        / elapsed;
+    let price_y = end
+        .price_y_cumulative
+        .wrapping_sub(start.price_y_cumulative)
+        / elapsed;
+
+    Ok((price_x, price_y))
+}
+
+// rejects the instruction once the unix timestamp `deadline` has passed
+pub fn check_deadline(deadline: Option<i64>) -> Result<()> {
+    if let Some(deadline) = deadline {
+        let now = Clock::get()?.unix_timestamp;
+        require!(now <= deadline, PoolError::Expired);
+    }
+
+    Ok(())
+}
+
+// only token-2022 extensions the pool can account for are accepted, anything that lets
+// a third party move or freeze vault funds (permanent delegate, non transferable,
+// transfer hook, ...) is rejected. transfer fees are accounted for in every transfer
+pub fn validate_mint_extensions(mint: &AccountInfo) -> Result<()> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+
+    for extension in mint.get_extension_types()? {
+        match extension {
+            ExtensionType::MetadataPointer
+            | ExtensionType::TokenMetadata
+            | ExtensionType::GroupPointer
+            | ExtensionType::TokenGroup
+            | ExtensionType::GroupMemberPointer
+            | ExtensionType::TokenGroupMember
+            | ExtensionType::InterestBearingConfig
+            | ExtensionType::TransferFeeConfig => {}
+            _ => return err!(PoolError::UnsupportedMintExtension),
+        }
+    }
+
+    Ok(())
+}
+
+// token-2022 transfer fee withheld when `amount` is sent, zero for mints without a
+// transfer fee. the recipient gets `amount - fee`, so handlers price what the vault
+// actually receives and check slippage on what the user actually receives
+pub fn get_transfer_fee(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(0);
+    }
+
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+
+    let fee = match mint.get_extension::<TransferFeeConfig>() {
+        Ok(config) => config
+            .calculate_epoch_fee(Clock::get()?.epoch, amount)
+            .ok_or(PoolError::OverFlowError)?,
+        Err(_) => 0,
+    };
+
+    Ok(fee)
+}
+
+// transfer fee to add on top of `amount` so the recipient gets exactly `amount`,
+// the sender (the user on the way in, the vault on the way out) pays it
+pub fn get_transfer_inverse_fee(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(0);
+    }
+
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+
+    let fee = match mint.get_extension::<TransferFeeConfig>() {
+        Ok(config) => config
+            .calculate_inverse_epoch_fee(Clock::get()?.epoch, amount)
+            .ok_or(PoolError::OverFlowError)?,
+        Err(_) => 0,
+    };
+
+    Ok(fee)
+}
+
+// tops the user's wsol account up with native sol so it holds at least `amount`,
+// does nothing for other mints or when the account already holds enough
+pub fn wrap_sol<'info>(
+    user: &Signer<'info>,
+    user_ata: &InterfaceAccount<'info, TokenAccount>,
+    amount: u64,
+    system_program: &Program<'info, System>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
+    if !user_ata.is_native() {
+        return Ok(());
+    }
+
+    let shortfall = amount.saturating_sub(user_ata.amount);
+    if shortfall == 0 {
+        return Ok(());
+    }
+
+    let cpi_accounts = system_program::Transfer {
+        from: user.to_account_info(),
+        to: user_ata.to_account_info(),
+    };
+    let ctx = CpiContext::new(system_program.to_account_info(), cpi_accounts);
+    system_program::transfer(ctx, shortfall)?;
+
+    let cpi_accounts = SyncNative {
+        account: user_ata.to_account_info(),
+    };
+    let ctx = CpiContext::new(token_program.to_account_info(), cpi_accounts);
+    sync_native(ctx)?;
+
+    Ok(())
+}
+
+// closes the user's wsol account so its whole balance (and rent) comes back as native sol
+pub fn unwrap_sol<'info>(
+    user: &Signer<'info>,
+    user_ata: &InterfaceAccount<'info, TokenAccount>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
+    let cpi_accounts = CloseAccount {
+        account: user_ata.to_account_info(),
+        destination: user.to_account_info(),
+        authority: user.to_account_info(),
+    };
+    let ctx = CpiContext::new(token_program.to_account_info(), cpi_accounts);
+    close_account(ctx)?;
+
+    Ok(())
+}
+
+// addresses of the canonical pool for a mint pair and fee tier
+pub struct PoolAddresses {
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub pool: Pubkey,
+    pub pool_bump: u8,
+    pub lp_mint: Pubkey,
+    pub ata_x: Pubkey,
+    pub ata_y: Pubkey,
+    pub locked_lp_ata: Pubkey,
+}
+
+// derives the pool, lp mint and vault addresses for a pair, the mints can be passed in any order
+// together with the token program that owns each of them
+pub fn get_pool_addresses(
+    (mint_a, token_program_a): (Pubkey, Pubkey),
+    (mint_b, token_program_b): (Pubkey, Pubkey),
+    fee_tier: u16,
+) -> PoolAddresses {
+    // pools always store the pair sorted, mint_x < mint_y
+    let ((mint_x, token_program_x), (mint_y, token_program_y)) = match mint_a < mint_b {
+        true => ((mint_a, token_program_a), (mint_b, token_program_b)),
+        false => ((mint_b, token_program_b), (mint_a, token_program_a)),
+    };
+
+    let (pool, pool_bump) = Pubkey::find_program_address(
+        &[
+            POOL_SEED.as_bytes(),
+            mint_x.as_ref(),
+            mint_y.as_ref(),
+            &fee_tier.to_le_bytes(),
+        ],
+        &crate::ID,
+    );
+
+    let (lp_mint, _) =
+        Pubkey::find_program_address(&[LP_TOKEN_MINT_SEED.as_bytes(), pool.as_ref()], &crate::ID);
+
+    PoolAddresses {
+        mint_x,
+        mint_y,
+        pool,
+        pool_bump,
+        lp_mint,
+        ata_x: get_associated_token_address_with_program_id(&pool, &mint_x, &token_program_x),
+        ata_y: get_associated_token_address_with_program_id(&pool, &mint_y, &token_program_y),
+        locked_lp_ata: get_associated_token_address(&pool, &lp_mint),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_sided_swap_amount_balanced() {
+        // without a fee s = R (sqrt(1 + A / R) - 1), so A = 3R swaps exactly R
+        assert_eq!(
+            get_single_sided_swap_amount(3_000_000, 1_000_000, 0).unwrap(),
+            1_000_000
+        );
+        // A = R swaps a bit more than R (sqrt(2) - 1) to make up for the fee
+        assert_eq!(
+            get_single_sided_swap_amount(1_000_000, 1_000_000, 30).unwrap(),
+            414_835
+        );
+    }
+
+    #[test]
+    fn single_sided_swap_amount_tiny() {
+        assert_eq!(get_single_sided_swap_amount(0, 1_000_000, 30).unwrap(), 0);
+        assert_eq!(get_single_sided_swap_amount(1, 1_000_000, 30).unwrap(), 0);
+        // next to a huge reserve about half of the deposit is swapped
+        assert_eq!(
+            get_single_sided_swap_amount(1_000, u64::MAX, 30).unwrap(),
+            500
+        );
+        assert!(get_single_sided_swap_amount(1_000, 0, 30).is_err());
+    }
+
+    #[test]
+    fn single_sided_swap_amount_u64_max() {
+        // fits u128 without scaling
+        assert_eq!(
+            get_single_sided_swap_amount(u64::MAX, 1_000, 30).unwrap(),
+            136_022_978_035
+        );
 
-    Ok((required_x, required_y))
+        // both products overflow u128 and are scaled down, only low order bits are lost
+        let exact = 7_652_372_661_230_561_855u64;
+        let swap_amount = get_single_sided_swap_amount(u64::MAX, u64::MAX, 30).unwrap();
+        assert!(swap_amount.abs_diff(exact) <= exact >> 48);
+    }
 }
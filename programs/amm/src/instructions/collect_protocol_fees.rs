@@ -0,0 +1,148 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+
+use crate::{
+    error::PoolError, events::ProtocolFeesCollected, AmmConfig, Pool, AMM_CONFIG_SEED, POOL_SEED,
+};
+
+#[derive(Accounts)]
+pub struct CollectProtocolFees<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mint::token_program = token_program_x)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_program_y)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::token_program = token_program_x,
+        associated_token::authority = pool,
+    )]
+    pub ata_x: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::token_program = token_program_y,
+        associated_token::authority = pool,
+    )]
+    pub ata_y: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [AMM_CONFIG_SEED.as_bytes()],
+        bump = config.bump
+    )]
+    pub config: Account<'info, AmmConfig>,
+
+    #[account(
+        mut,
+        has_one = authority,
+        has_one = mint_x,
+        has_one = mint_y,
+        seeds = [
+            POOL_SEED.as_bytes(),
+            pool.mint_x.as_ref(),
+            pool.mint_y.as_ref(),
+            &pool.fee_tier.to_le_bytes(),
+        ],
+        bump = pool.pool_bump
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+        mut,
+        token::mint = mint_x,
+        token::token_program = token_program_x,
+        token::authority = config.treasury,
+    )]
+    pub treasury_ata_x: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = mint_y,
+        token::token_program = token_program_y,
+        token::authority = config.treasury,
+    )]
+    pub treasury_ata_y: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
+}
+
+impl<'info> CollectProtocolFees<'info> {
+    pub fn collect_protocol_fees(&mut self) -> Result<()> {
+        require!(!self.config.paused, PoolError::AmmPaused);
+
+        let amount_x = self.pool.fee_collected_x;
+        let amount_y = self.pool.fee_collected_y;
+
+        if amount_x > 0 {
+            self.transfer_to_treasury(true, amount_x)?;
+        }
+        if amount_y > 0 {
+            self.transfer_to_treasury(false, amount_y)?;
+        }
+
+        self.pool.fee_collected_x = 0;
+        self.pool.fee_collected_y = 0;
+
+        emit!(ProtocolFeesCollected {
+            pool: self.pool.key(),
+            treasury: self.config.treasury,
+            amount_x,
+            amount_y,
+        });
+
+        Ok(())
+    }
+
+    fn transfer_to_treasury(&mut self, is_x: bool, amount: u64) -> Result<()> {
+        let (from, to, mint, decimals, cpi_program) = match is_x {
+            true => (
+                self.ata_x.to_account_info(),
+                self.treasury_ata_x.to_account_info(),
+                self.mint_x.to_account_info(),
+                self.mint_x.decimals,
+                self.token_program_x.to_account_info(),
+            ),
+            false => (
+                self.ata_y.to_account_info(),
+                self.treasury_ata_y.to_account_info(),
+                self.mint_y.to_account_info(),
+                self.mint_y.decimals,
+                self.token_program_y.to_account_info(),
+            ),
+        };
+
+        let mint_x = self.pool.mint_x;
+        let mint_y = self.pool.mint_y;
+        let fee_tier = self.pool.fee_tier.to_le_bytes();
+
+        let seeds = &[
+            POOL_SEED.as_bytes(),
+            mint_x.as_ref(),
+            mint_y.as_ref(),
+            fee_tier.as_ref(),
+            &[self.pool.pool_bump],
+        ];
+
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint,
+            to,
+            authority: self.pool.to_account_info(),
+        };
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        transfer_checked(ctx, amount, decimals)?;
+
+        Ok(())
+    }
+}
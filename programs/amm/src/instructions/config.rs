@@ -0,0 +1,88 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::PoolError, helper::validate_fee_tiers, program::Amm, AmmConfig, AMM_CONFIG_SEED,
+};
+
+// the config is created once, only the program upgrade authority can do it
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + AmmConfig::INIT_SPACE,
+        seeds = [AMM_CONFIG_SEED.as_bytes()],
+        bump
+    )]
+    pub config: Account<'info, AmmConfig>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, Amm>,
+
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(admin.key()) @ PoolError::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeConfig<'info> {
+    pub fn initialize_config(
+        &mut self,
+        fee_tiers: Vec<u16>,
+        protocol_fee_share_bp: u16,
+        treasury: Pubkey,
+        bumps: InitializeConfigBumps,
+    ) -> Result<()> {
+        validate_fee_tiers(&fee_tiers, protocol_fee_share_bp)?;
+
+        self.config.set_inner(AmmConfig {
+            admin: self.admin.key(),
+            fee_tiers,
+            protocol_fee_share_bp,
+            treasury,
+            paused: false,
+            bump: bumps.config,
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct ConfigOps<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = admin @ PoolError::Unauthorized,
+        seeds = [AMM_CONFIG_SEED.as_bytes()],
+        bump = config.bump
+    )]
+    pub config: Account<'info, AmmConfig>,
+}
+
+impl<'info> ConfigOps<'info> {
+    // new tiers and share only apply to pools created afterwards
+    pub fn update_config(
+        &mut self,
+        fee_tiers: Vec<u16>,
+        protocol_fee_share_bp: u16,
+        treasury: Pubkey,
+    ) -> Result<()> {
+        validate_fee_tiers(&fee_tiers, protocol_fee_share_bp)?;
+
+        self.config.fee_tiers = fee_tiers;
+        self.config.protocol_fee_share_bp = protocol_fee_share_bp;
+        self.config.treasury = treasury;
+        Ok(())
+    }
+
+    pub fn set_paused(&mut self, paused: bool) -> Result<()> {
+        self.config.paused = paused;
+        Ok(())
+    }
+}
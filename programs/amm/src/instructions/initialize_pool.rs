@@ -1,81 +1,165 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{Mint, Token, TokenAccount},
+    token::Token,
+    token_interface::{Mint, TokenAccount, TokenInterface},
 };
 
-use crate::{Pool, LP_TOKEN_MINT_SEED, POOL_SEED};
+use crate::{
+    error::PoolError,
+    helper::{split_fee_tier, validate_mint_extensions},
+    AmmConfig, Observations, Pool, AMM_CONFIG_SEED, LP_TOKEN_MINT_SEED, OBSERVATIONS_SEED,
+    POOL_SEED,
+};
 
 #[derive(Accounts)]
+#[instruction(fee_tier_index: u8)]
 pub struct Initialize<'info> {
     #[account(mut)]
     pub authority: Signer<'info>,
 
-    pub mint_x: Account<'info, Mint>,
-    pub mint_y: Account<'info, Mint>,
+    #[account(
+        seeds = [AMM_CONFIG_SEED.as_bytes()],
+        bump = config.bump,
+        // canonical pools are created by the config admin, who becomes their authority
+        constraint = config.admin == authority.key() @ PoolError::Unauthorized,
+        constraint = !config.paused @ PoolError::AmmPaused,
+    )]
+    pub config: Account<'info, AmmConfig>,
+
+    #[account(mint::token_program = token_program_x)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_program_y)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
 
+    // anchor creates every init account before it checks the others, so the pair and tier
+    // checks sit on the first init to run before the rest of the pool is created.
+    // mints are passed sorted so every pair has a single canonical pool per fee tier
     #[account(
         init,
         payer = authority,
         associated_token::mint = mint_x,
+        associated_token::token_program = token_program_x,
         associated_token::authority = pool,
+        constraint = mint_y.key() != mint_x.key() @ PoolError::SameMint,
+        constraint = mint_x.key() < mint_y.key() @ PoolError::InvalidMintOrder,
+        constraint = (fee_tier_index as usize) < config.fee_tiers.len() @ PoolError::InvalidFeeTier,
     )]
-    pub ata_x: Account<'info, TokenAccount>,
+    pub ata_x: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         init,
         payer = authority,
         associated_token::mint = mint_y,
+        associated_token::token_program = token_program_y,
         associated_token::authority = pool,
     )]
-    pub ata_y: Account<'info, TokenAccount>,
+    pub ata_y: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         init,
         payer = authority,
         mint::decimals = 6,
         mint::authority = pool,
+        mint::token_program = token_program,
         seeds = [LP_TOKEN_MINT_SEED.as_bytes(),pool.key().as_ref()],
         bump
     )]
-    pub lp_token_mint: Account<'info, Mint>,
+    pub lp_token_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         init,
         payer = authority,
         space = 8 + Pool::INIT_SPACE,
-        seeds = [POOL_SEED.as_bytes(),authority.key().as_ref()],
+        seeds = [
+            POOL_SEED.as_bytes(),
+            mint_x.key().as_ref(),
+            mint_y.key().as_ref(),
+            &config
+                .fee_tiers
+                .get(fee_tier_index as usize)
+                .copied()
+                .unwrap_or_default()
+                .to_le_bytes(),
+        ],
         bump
     )]
     pub pool: Account<'info, Pool>,
 
+    #[account(
+        init,
+        payer = authority,
+        space = Observations::space(1),
+        seeds = [OBSERVATIONS_SEED.as_bytes(), pool.key().as_ref()],
+        bump
+    )]
+    pub observations: AccountLoader<'info, Observations>,
+
+    // holds the MINIMUM_LIQUIDITY lp tokens locked on the first deposit
+    #[account(
+        init,
+        payer = authority,
+        associated_token::mint = lp_token_mint,
+        associated_token::token_program = token_program,
+        associated_token::authority = pool,
+    )]
+    pub locked_lp_ata: InterfaceAccount<'info, TokenAccount>,
+
     pub associated_token_program: Program<'info, AssociatedToken>,
+    // lp mint is always a classic spl token mint
     pub token_program: Program<'info, Token>,
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> Initialize<'info> {
-    pub fn initialize_pool(
-        &mut self,
-        fee_bp: u16,
-        lp_supply: u64,
-        bumps: InitializeBumps,
-    ) -> Result<()> {
+    pub fn initialize_pool(&mut self, fee_tier_index: u8, bumps: InitializeBumps) -> Result<()> {
+        validate_mint_extensions(&self.mint_x.to_account_info())?;
+        validate_mint_extensions(&self.mint_y.to_account_info())?;
+
+        let fee_tier = self.config.fee_tiers[fee_tier_index as usize];
+
+        let (lp_fee_bp, protocol_fee_bp) =
+            split_fee_tier(fee_tier, self.config.protocol_fee_share_bp)?;
+
+        let clock = Clock::get()?;
+
         self.pool.set_inner(Pool {
             authority: self.authority.key(),
+            pending_authority: None,
             mint_x: self.mint_x.key(),
             mint_y: self.mint_y.key(),
+            fee_tier,
             ata_x: self.ata_x.key(),
             ata_y: self.ata_y.key(),
             lp_mint: self.lp_token_mint.key(),
-            lp_supply,
-            fee_bp,
+            lp_fee_bp,
+            protocol_fee_bp,
+            reserve_x: 0,
+            reserve_y: 0,
             fee_collected_x: 0,
             fee_collected_y: 0,
+            price_x_cumulative: 0,
+            price_y_cumulative: 0,
+            last_update_timestamp: clock.unix_timestamp,
             pool_bump: bumps.pool,
             lp_bump: bumps.lp_token_mint,
             locked: false,
         });
+
+        // the first observation is the pool's creation, the buffer starts with one slot
+        {
+            let mut observations = self.observations.load_init()?;
+            observations.pool = self.pool.key();
+            observations.cardinality = 1;
+            observations.cardinality_next = 1;
+            observations.bump = bumps.observations;
+        }
+
+        let (mut header, mut slots) = Observations::load_buffer_mut(&self.observations)?;
+        header.write(&mut slots, &self.pool, clock.slot);
+
         Ok(())
     }
 }
@@ -9,3 +9,21 @@ pub use swap::*;
 
 pub mod withdraw;
 pub use withdraw::*;
+
+pub mod ops;
+pub use ops::*;
+
+pub mod collect_protocol_fees;
+pub use collect_protocol_fees::*;
+
+pub mod skim;
+pub use skim::*;
+
+pub mod sync;
+pub use sync::*;
+
+pub mod config;
+pub use config::*;
+
+pub mod oracle;
+pub use oracle::*;
@@ -0,0 +1,139 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::PoolError,
+    events::{AuthorityTransferred, FeeUpdated},
+    helper::{split_fee_tier, validate_fees},
+    AmmConfig, Pool, AMM_CONFIG_SEED, POOL_SEED,
+};
+
+// admin operations on an existing pool, only the pool authority can sign these
+#[derive(Accounts)]
+pub struct PoolOps<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [
+            POOL_SEED.as_bytes(),
+            pool.mint_x.as_ref(),
+            pool.mint_y.as_ref(),
+            &pool.fee_tier.to_le_bytes(),
+        ],
+        bump = pool.pool_bump
+    )]
+    pub pool: Account<'info, Pool>,
+}
+
+impl<'info> PoolOps<'info> {
+    pub fn set_fee(&mut self, lp_fee_bp: u16, protocol_fee_bp: u16) -> Result<()> {
+        validate_fees(lp_fee_bp, protocol_fee_bp)?;
+        apply_fees(&mut self.pool, lp_fee_bp, protocol_fee_bp);
+        Ok(())
+    }
+
+    // emergency brake: blocks swaps and deposits, withdrawals stay open
+    pub fn lock_pool(&mut self) -> Result<()> {
+        self.pool.locked = true;
+        Ok(())
+    }
+
+    pub fn unlock_pool(&mut self) -> Result<()> {
+        self.pool.locked = false;
+        Ok(())
+    }
+
+    // first step of the authority transfer, the new authority has to accept it
+    pub fn propose_authority(&mut self, new_authority: Pubkey) -> Result<()> {
+        self.pool.pending_authority = Some(new_authority);
+        Ok(())
+    }
+}
+
+// re-applies the config's protocol fee share to the pool's fee tier, the tier itself is part
+// of the pool address and never changes
+#[derive(Accounts)]
+pub struct UpdateFee<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [AMM_CONFIG_SEED.as_bytes()],
+        bump = config.bump
+    )]
+    pub config: Account<'info, AmmConfig>,
+
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [
+            POOL_SEED.as_bytes(),
+            pool.mint_x.as_ref(),
+            pool.mint_y.as_ref(),
+            &pool.fee_tier.to_le_bytes(),
+        ],
+        bump = pool.pool_bump
+    )]
+    pub pool: Account<'info, Pool>,
+}
+
+impl<'info> UpdateFee<'info> {
+    pub fn update_fee(&mut self) -> Result<()> {
+        let (lp_fee_bp, protocol_fee_bp) =
+            split_fee_tier(self.pool.fee_tier, self.config.protocol_fee_share_bp)?;
+
+        apply_fees(&mut self.pool, lp_fee_bp, protocol_fee_bp);
+        Ok(())
+    }
+}
+
+fn apply_fees(pool: &mut Account<Pool>, lp_fee_bp: u16, protocol_fee_bp: u16) {
+    let old_lp_fee_bp = pool.lp_fee_bp;
+    let old_protocol_fee_bp = pool.protocol_fee_bp;
+
+    pool.lp_fee_bp = lp_fee_bp;
+    pool.protocol_fee_bp = protocol_fee_bp;
+
+    emit!(FeeUpdated {
+        pool: pool.key(),
+        old_lp_fee_bp,
+        old_protocol_fee_bp,
+        new_lp_fee_bp: lp_fee_bp,
+        new_protocol_fee_bp: protocol_fee_bp,
+    });
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    pub new_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = pool.pending_authority == Some(new_authority.key()) @ PoolError::InvalidPendingAuthority,
+        seeds = [
+            POOL_SEED.as_bytes(),
+            pool.mint_x.as_ref(),
+            pool.mint_y.as_ref(),
+            &pool.fee_tier.to_le_bytes(),
+        ],
+        bump = pool.pool_bump
+    )]
+    pub pool: Account<'info, Pool>,
+}
+
+impl<'info> AcceptAuthority<'info> {
+    pub fn accept_authority(&mut self) -> Result<()> {
+        let old_authority = self.pool.authority;
+
+        self.pool.authority = self.new_authority.key();
+        self.pool.pending_authority = None;
+
+        emit!(AuthorityTransferred {
+            pool: self.pool.key(),
+            old_authority,
+            new_authority: self.pool.authority,
+        });
+
+        Ok(())
+    }
+}
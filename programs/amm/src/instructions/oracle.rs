@@ -0,0 +1,95 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::PoolError, Observations, Pool, PriceObservation, MAX_OBSERVE_QUERIES, OBSERVATIONS_SEED,
+    OBSERVATION_CAPACITY, POOL_SEED,
+};
+
+// permissionless, grows the number of observations the pool keeps. the caller pays the
+// rent for the new slots, they are used once the writes wrap around to the end of the
+// current ones
+#[derive(Accounts)]
+#[instruction(cardinality_next: u16)]
+pub struct IncreaseObservationCardinality<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [
+            POOL_SEED.as_bytes(),
+            pool.mint_x.as_ref(),
+            pool.mint_y.as_ref(),
+            &pool.fee_tier.to_le_bytes(),
+        ],
+        bump = pool.pool_bump
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+        mut,
+        seeds = [OBSERVATIONS_SEED.as_bytes(), pool.key().as_ref()],
+        bump = observations.load()?.bump,
+        realloc = Observations::space(cardinality_next),
+        realloc::payer = payer,
+        realloc::zero = false,
+    )]
+    pub observations: AccountLoader<'info, Observations>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> IncreaseObservationCardinality<'info> {
+    pub fn increase_observation_cardinality(&mut self, cardinality_next: u16) -> Result<()> {
+        let mut observations = self.observations.load_mut()?;
+
+        require!(
+            cardinality_next > observations.cardinality_next
+                && cardinality_next <= OBSERVATION_CAPACITY,
+            PoolError::InvalidObservationCardinality
+        );
+
+        observations.cardinality_next = cardinality_next;
+
+        Ok(())
+    }
+}
+
+// read only, meant to be simulated (`.view()`) by integrators
+#[derive(Accounts)]
+pub struct Observe<'info> {
+    #[account(
+        seeds = [
+            POOL_SEED.as_bytes(),
+            pool.mint_x.as_ref(),
+            pool.mint_y.as_ref(),
+            &pool.fee_tier.to_le_bytes(),
+        ],
+        bump = pool.pool_bump
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+        seeds = [OBSERVATIONS_SEED.as_bytes(), pool.key().as_ref()],
+        bump = observations.load()?.bump
+    )]
+    pub observations: AccountLoader<'info, Observations>,
+}
+
+impl<'info> Observe<'info> {
+    // cumulative prices `seconds_ago[i]` seconds before now, a TWAP between two of them
+    // is given by `helper::get_twap`
+    pub fn observe(&self, seconds_ago: Vec<u32>) -> Result<Vec<PriceObservation>> {
+        require!(
+            seconds_ago.len() <= MAX_OBSERVE_QUERIES,
+            PoolError::TooManyObservationQueries
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let (header, slots) = Observations::load_buffer(&self.observations)?;
+
+        seconds_ago
+            .into_iter()
+            .map(|seconds_ago| header.observe(&slots, &self.pool, now, seconds_ago))
+            .collect()
+    }
+}
@@ -1,78 +1,123 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{mint_to, transfer, Mint, MintTo, Token, TokenAccount, Transfer},
+    token::Token,
+    token_interface::{
+        mint_to, transfer_checked, Mint, MintTo, TokenAccount, TokenInterface, TransferChecked,
+    },
 };
 
 use crate::{
     error::PoolError,
-    helper::{get_lp_token_amount_init, get_lp_tokens_to_mint, xy_from_l},
-    Pool, LP_TOKEN_MINT_SEED, POOL_SEED,
+    helper::{
+        check_deadline, get_amount_out, get_fee_amount, get_lp_token_amount_init,
+        get_lp_tokens_to_mint, get_single_sided_swap_amount, get_transfer_fee,
+        get_transfer_inverse_fee, wrap_sol, xy_from_l,
+    },
+    AmmConfig, Observations, Pool, AMM_CONFIG_SEED, LP_TOKEN_MINT_SEED, MINIMUM_LIQUIDITY,
+    OBSERVATIONS_SEED, POOL_SEED,
 };
 
 #[derive(Accounts)]
 pub struct ProvideLiquidity<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
-
     #[account(mut)]
     pub user: Signer<'info>,
 
-    pub mint_x: Account<'info, Mint>,
-    pub mint_y: Account<'info, Mint>,
+    #[account(mint::token_program = token_program_x)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_program_y)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         associated_token::mint = mint_x,
+        associated_token::token_program = token_program_x,
         associated_token::authority = pool,
     )]
-    pub ata_x: Account<'info, TokenAccount>,
+    pub ata_x: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         associated_token::mint = mint_y,
+        associated_token::token_program = token_program_y,
         associated_token::authority = pool,
     )]
-    pub ata_y: Account<'info, TokenAccount>,
+    pub ata_y: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         seeds = [LP_TOKEN_MINT_SEED.as_bytes(),pool.key().as_ref()],
         bump = pool.lp_bump
     )]
-    pub lp_token_mint: Account<'info, Mint>,
+    pub lp_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [AMM_CONFIG_SEED.as_bytes()],
+        bump = config.bump
+    )]
+    pub config: Account<'info, AmmConfig>,
 
     #[account(
         mut,
-        seeds = [POOL_SEED.as_bytes(),authority.key().as_ref()],
+        has_one = mint_x,
+        has_one = mint_y,
+        seeds = [
+            POOL_SEED.as_bytes(),
+            pool.mint_x.as_ref(),
+            pool.mint_y.as_ref(),
+            &pool.fee_tier.to_le_bytes(),
+        ],
         bump = pool.pool_bump
     )]
     pub pool: Account<'info, Pool>,
 
     #[account(
         mut,
+        seeds = [OBSERVATIONS_SEED.as_bytes(), pool.key().as_ref()],
+        bump = observations.load()?.bump
+    )]
+    pub observations: AccountLoader<'info, Observations>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
         associated_token::mint = mint_x,
+        associated_token::token_program = token_program_x,
         associated_token::authority = user
     )]
-    pub user_ata_x: Account<'info, TokenAccount>,
+    pub user_ata_x: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
-        mut,
+        init_if_needed,
+        payer = user,
         associated_token::mint = mint_y,
+        associated_token::token_program = token_program_y,
         associated_token::authority = user
     )]
-    pub user_ata_y: Account<'info, TokenAccount>,
+    pub user_ata_y: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
-        init,
+        init_if_needed,
         payer = user,
         associated_token::mint = lp_token_mint,
+        associated_token::token_program = token_program,
         associated_token::authority = user
     )]
-    pub user_lp_ata: Account<'info, TokenAccount>,
+    pub user_lp_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = lp_token_mint,
+        associated_token::token_program = token_program,
+        associated_token::authority = pool,
+    )]
+    pub locked_lp_ata: InterfaceAccount<'info, TokenAccount>,
 
     pub associated_token_program: Program<'info, AssociatedToken>,
+    // lp mint is always a classic spl token mint
     pub token_program: Program<'info, Token>,
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
@@ -82,88 +127,298 @@ impl<'info> ProvideLiquidity<'info> {
         // lp_token_asking: u64,
         max_x_token: u64,
         max_y_token: u64,
+        min_lp_out: u64,
+        wrap_unwrap_sol: bool,
+        deadline: Option<i64>,
     ) -> Result<()> {
-        require!(self.pool.locked == false, PoolError::PoolIsLocked);
+        check_deadline(deadline)?;
+        require!(!self.pool.locked, PoolError::PoolIsLocked);
+        require!(!self.config.paused, PoolError::AmmPaused);
+
+        self.pool.record(&self.observations, &Clock::get()?)?;
+
+        let is_first_deposit = self.lp_token_mint.supply == 0;
 
-        let (x, y, amount) = match self.lp_token_mint.supply == 0
-            && self.ata_x.amount == 0
-            && self.ata_y.amount == 0
-        {
+        let max_x_net =
+            max_x_token - get_transfer_fee(&self.mint_x.to_account_info(), max_x_token)?;
+        let max_y_net =
+            max_y_token - get_transfer_fee(&self.mint_y.to_account_info(), max_y_token)?;
+
+        let (x, y, amount) = match is_first_deposit {
             true => {
-                let lp_tokens_to_mint = get_lp_token_amount_init(max_x_token, max_y_token).unwrap();
-                (max_x_token, max_y_token, lp_tokens_to_mint)
+                let lp_tokens_to_mint = get_lp_token_amount_init(max_x_net, max_y_net)?;
+
+                // first depositor has to cover the locked liquidity, which makes
+                // inflating the share price with a tiny first deposit too expensive
+                require!(
+                    lp_tokens_to_mint > MINIMUM_LIQUIDITY,
+                    PoolError::InsufficientInitialLiquidity
+                );
+
+                (max_x_net, max_y_net, lp_tokens_to_mint - MINIMUM_LIQUIDITY)
             }
             false => {
                 let total_lp_supply = self.lp_token_mint.supply;
-                let vault_x = self.ata_x.amount;
-                let vault_y = self.ata_y.amount;
-
-                let lp_tokens_to_mint = get_lp_tokens_to_mint(
-                    total_lp_supply,
-                    vault_x,
-                    vault_y,
-                    max_x_token,
-                    max_y_token,
-                )
-                .unwrap();
+                let vault_x = self.pool.reserve_x;
+                let vault_y = self.pool.reserve_y;
+
+                let lp_tokens_to_mint =
+                    get_lp_tokens_to_mint(total_lp_supply, vault_x, vault_y, max_x_net, max_y_net)?;
 
                 let (required_x, required_y) =
-                    xy_from_l(total_lp_supply, vault_x, vault_y, lp_tokens_to_mint).unwrap();
+                    xy_from_l(total_lp_supply, vault_x, vault_y, lp_tokens_to_mint)?;
 
                 (required_x, required_y, lp_tokens_to_mint)
             }
         };
 
-        require!(x <= max_x_token, PoolError::SlippageExceeded);
-        require!(y <= max_y_token, PoolError::SlippageExceeded);
+        // lp amount is rounded down, never mint 0 lp for a non zero deposit
+        require!(amount > 0, PoolError::LpTokenAmountCannotBeZero);
+        require!(amount >= min_lp_out, PoolError::SlippageExceeded);
+
+        let x_gross = x
+            .checked_add(get_transfer_inverse_fee(&self.mint_x.to_account_info(), x)?)
+            .ok_or(PoolError::OverFlowError)?;
+        let y_gross = y
+            .checked_add(get_transfer_inverse_fee(&self.mint_y.to_account_info(), y)?)
+            .ok_or(PoolError::OverFlowError)?;
+
+        require!(x_gross <= max_x_token, PoolError::SlippageExceeded);
+        require!(y_gross <= max_y_token, PoolError::SlippageExceeded);
+
+        // tokens go in first, lp is only minted once both vaults received exactly x and y
+        self.token_depositor(true, x_gross, x, wrap_unwrap_sol)?;
+        self.token_depositor(false, y_gross, y, wrap_unwrap_sol)?;
+
+        if is_first_deposit {
+            self.mint_lp_tokens(self.locked_lp_ata.to_account_info(), MINIMUM_LIQUIDITY)?;
+        }
+        self.mint_lp_tokens(self.user_lp_ata.to_account_info(), amount)?;
+
+        self.pool.reserve_x = self
+            .pool
+            .reserve_x
+            .checked_add(x)
+            .ok_or(PoolError::OverFlowError)?;
+        self.pool.reserve_y = self
+            .pool
+            .reserve_y
+            .checked_add(y)
+            .ok_or(PoolError::OverFlowError)?;
+
+        Ok(())
+    }
+
+    // single sided deposit: swaps the optimal part of `amount_in` through the pool's own
+    // curve and deposits the rest together with the swap output
+    pub fn deposit_single(
+        &mut self,
+        amount_in: u64,
+        is_x: bool,
+        min_lp_out: u64,
+        wrap_unwrap_sol: bool,
+        deadline: Option<i64>,
+    ) -> Result<()> {
+        check_deadline(deadline)?;
+        require!(!self.pool.locked, PoolError::PoolIsLocked);
+        require!(!self.config.paused, PoolError::AmmPaused);
+        require!(amount_in > 0, PoolError::InvalidAmount);
+
+        self.pool.record(&self.observations, &Clock::get()?)?;
+
+        // there is no ratio to zap into before the first deposit
+        let total_lp_supply = self.lp_token_mint.supply;
+        require!(total_lp_supply > 0, PoolError::ZeroReserves);
+
+        let mint_in = match is_x {
+            true => self.mint_x.to_account_info(),
+            false => self.mint_y.to_account_info(),
+        };
+
+        let amount_in_net = amount_in - get_transfer_fee(&mint_in, amount_in)?;
+
+        let vault_x = self.pool.reserve_x;
+        let vault_y = self.pool.reserve_y;
+
+        let (reserve_in, reserve_out) = match is_x {
+            true => (vault_x, vault_y),
+            false => (vault_y, vault_x),
+        };
+
+        let total_fee_bp = self.pool.total_fee_bp();
+
+        let swap_amount = get_single_sided_swap_amount(amount_in_net, reserve_in, total_fee_bp)?;
+        let swap_out = get_amount_out(swap_amount, reserve_in, reserve_out, total_fee_bp)?;
+
+        let protocol_fee = get_fee_amount(swap_amount, self.pool.protocol_fee_bp)?;
+
+        // reserves after the virtual swap, the lp fee stays in and the protocol fee is set aside
+        let reserve_in = reserve_in
+            .checked_add(swap_amount - protocol_fee)
+            .ok_or(PoolError::OverFlowError)?;
+        let reserve_out = reserve_out
+            .checked_sub(swap_out)
+            .ok_or(PoolError::OutputExceedsReserves)?;
+
+        let amount_left = amount_in_net - swap_amount;
 
-        let _ = self.token_depositor(true, x);
-        let _ = self.token_depositor(false, y);
+        let (vault_x, vault_y, max_x_token, max_y_token) = match is_x {
+            true => (reserve_in, reserve_out, amount_left, swap_out),
+            false => (reserve_out, reserve_in, swap_out, amount_left),
+        };
+
+        let lp_tokens_to_mint =
+            get_lp_tokens_to_mint(total_lp_supply, vault_x, vault_y, max_x_token, max_y_token)?;
+
+        require!(lp_tokens_to_mint > 0, PoolError::LpTokenAmountCannotBeZero);
+        require!(lp_tokens_to_mint >= min_lp_out, PoolError::SlippageExceeded);
+
+        let (required_x, required_y) =
+            xy_from_l(total_lp_supply, vault_x, vault_y, lp_tokens_to_mint)?;
+
+        // the swap output never leaves the vault, so only the input side is transferred.
+        // rounding dust of the output side (swap_out - required) stays in the pool
+        let required_in = match is_x {
+            true => required_x,
+            false => required_y,
+        };
+
+        let amount_deposited = swap_amount
+            .checked_add(required_in)
+            .ok_or(PoolError::OverFlowError)?;
+
+        let amount_deposited_gross = amount_deposited
+            .checked_add(get_transfer_inverse_fee(&mint_in, amount_deposited)?)
+            .ok_or(PoolError::OverFlowError)?;
+        require!(
+            amount_deposited_gross <= amount_in,
+            PoolError::SlippageExceeded
+        );
+
+        // the output side never leaves the vault, so only the input side reserve moves
+        let pool: &mut Pool = &mut self.pool;
+        let (reserve, fee_collected) = match is_x {
+            true => (&mut pool.reserve_x, &mut pool.fee_collected_x),
+            false => (&mut pool.reserve_y, &mut pool.fee_collected_y),
+        };
 
-        let _ = self.mint_lp_tokens(amount);
+        *fee_collected = fee_collected
+            .checked_add(protocol_fee)
+            .ok_or(PoolError::OverFlowError)?;
+        *reserve = reserve
+            .checked_add(amount_deposited - protocol_fee)
+            .ok_or(PoolError::OverFlowError)?;
+
+        self.token_depositor(
+            is_x,
+            amount_deposited_gross,
+            amount_deposited,
+            wrap_unwrap_sol,
+        )?;
+        self.mint_lp_tokens(self.user_lp_ata.to_account_info(), lp_tokens_to_mint)?;
 
         Ok(())
     }
 
-    fn token_depositor(&mut self, is_x: bool, amount: u64) -> Result<()> {
-        let (from, to) = match is_x {
+    // transfers `amount` from the user to the vault and checks the vault grew by exactly
+    // `amount_received`, so the reserves are never backed by less than tracked. the quote
+    // uses the mint's own transfer fee, this only trips if a transfer delivers anything else
+    fn token_depositor(
+        &mut self,
+        is_x: bool,
+        amount: u64,
+        amount_received: u64,
+        wrap: bool,
+    ) -> Result<()> {
+        // when asked to, native sol is wrapped into the user's wsol account if it holds too little
+        if wrap {
+            match is_x {
+                true => wrap_sol(
+                    &self.user,
+                    &self.user_ata_x,
+                    amount,
+                    &self.system_program,
+                    &self.token_program_x,
+                )?,
+                false => wrap_sol(
+                    &self.user,
+                    &self.user_ata_y,
+                    amount,
+                    &self.system_program,
+                    &self.token_program_y,
+                )?,
+            }
+        }
+
+        let vault_before = match is_x {
+            true => self.ata_x.amount,
+            false => self.ata_y.amount,
+        };
+
+        let (from, to, mint, decimals, cpi_program) = match is_x {
             true => (
                 self.user_ata_x.to_account_info(),
                 self.ata_x.to_account_info(),
+                self.mint_x.to_account_info(),
+                self.mint_x.decimals,
+                self.token_program_x.to_account_info(),
             ),
             false => (
                 self.user_ata_y.to_account_info(),
                 self.ata_y.to_account_info(),
+                self.mint_y.to_account_info(),
+                self.mint_y.decimals,
+                self.token_program_y.to_account_info(),
             ),
         };
 
-        let cpi_program = self.token_program.to_account_info();
-
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from,
+            mint,
             to,
             authority: self.user.to_account_info(),
         };
 
         let ctx = CpiContext::new(cpi_program, cpi_accounts);
 
-        transfer(ctx, amount)?;
+        transfer_checked(ctx, amount, decimals)?;
+
+        let vault_after = match is_x {
+            true => {
+                self.ata_x.reload()?;
+                self.ata_x.amount
+            }
+            false => {
+                self.ata_y.reload()?;
+                self.ata_y.amount
+            }
+        };
+
+        require!(
+            vault_after.checked_sub(vault_before) == Some(amount_received),
+            PoolError::TransferAmountMismatch
+        );
 
         Ok(())
     }
 
-    fn mint_lp_tokens(&mut self, amount: u64) -> Result<()> {
+    fn mint_lp_tokens(&mut self, to: AccountInfo<'info>, amount: u64) -> Result<()> {
         let cpi_program = self.token_program.to_account_info();
         let cpi_accounts = MintTo {
             mint: self.lp_token_mint.to_account_info(),
-            to: self.user_lp_ata.to_account_info(),
+            to,
             authority: self.pool.to_account_info(),
         };
 
-        let auth_key = self.authority.key();
+        let mint_x = self.pool.mint_x;
+        let mint_y = self.pool.mint_y;
+        let fee_tier = self.pool.fee_tier.to_le_bytes();
+
         let seeds = &[
             POOL_SEED.as_bytes(),
-            auth_key.as_ref(),
+            mint_x.as_ref(),
+            mint_y.as_ref(),
+            fee_tier.as_ref(),
             &[self.pool.pool_bump],
         ];
 
@@ -173,7 +428,6 @@ impl<'info> ProvideLiquidity<'info> {
 
         mint_to(ctx, amount)?;
 
-        self.pool.lp_supply += amount;
         Ok(())
     }
 }
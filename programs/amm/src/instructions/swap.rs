@@ -1,194 +1,352 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{mint_to, transfer, Mint, MintTo, Token, TokenAccount, Transfer},
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
 };
 
-use crate::{error::PoolError, swap, Pool, LP_TOKEN_MINT_SEED, POOL_SEED};
+use crate::{
+    error::PoolError,
+    helper::{
+        check_deadline, get_amount_in, get_amount_out, get_fee_amount, get_transfer_fee,
+        get_transfer_inverse_fee, unwrap_sol, wrap_sol,
+    },
+    swap, AmmConfig, Observations, Pool, AMM_CONFIG_SEED, OBSERVATIONS_SEED, POOL_SEED,
+};
 
 #[derive(Accounts)]
 pub struct SwapTokens<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
-
     #[account(mut)]
     pub user: Signer<'info>,
 
-    pub mint_x: Account<'info, Mint>,
-    pub mint_y: Account<'info, Mint>,
+    #[account(mint::token_program = token_program_x)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_program_y)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         associated_token::mint = mint_x,
+        associated_token::token_program = token_program_x,
         associated_token::authority = pool,
     )]
-    pub ata_x: Account<'info, TokenAccount>,
+    pub ata_x: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         associated_token::mint = mint_y,
+        associated_token::token_program = token_program_y,
         associated_token::authority = pool,
     )]
-    pub ata_y: Account<'info, TokenAccount>,
+    pub ata_y: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
-        mut,
-        seeds = [LP_TOKEN_MINT_SEED.as_bytes(),pool.key().as_ref()],
-        bump = pool.lp_bump
+        seeds = [AMM_CONFIG_SEED.as_bytes()],
+        bump = config.bump
     )]
-    pub lp_token_mint: Account<'info, Mint>,
+    pub config: Account<'info, AmmConfig>,
 
     #[account(
         mut,
-        seeds = [POOL_SEED.as_bytes(),authority.key().as_ref()],
+        has_one = mint_x,
+        has_one = mint_y,
+        seeds = [
+            POOL_SEED.as_bytes(),
+            pool.mint_x.as_ref(),
+            pool.mint_y.as_ref(),
+            &pool.fee_tier.to_le_bytes(),
+        ],
         bump = pool.pool_bump
     )]
     pub pool: Account<'info, Pool>,
 
     #[account(
         mut,
-        associated_token::mint = mint_x,
-        associated_token::authority = user
+        seeds = [OBSERVATIONS_SEED.as_bytes(), pool.key().as_ref()],
+        bump = observations.load()?.bump
     )]
-    pub user_ata_x: Account<'info, TokenAccount>,
+    pub observations: AccountLoader<'info, Observations>,
 
     #[account(
-        mut,
-        associated_token::mint = mint_y,
+        init_if_needed,
+        payer = user,
+        associated_token::mint = mint_x,
+        associated_token::token_program = token_program_x,
         associated_token::authority = user
     )]
-    pub user_ata_y: Account<'info, TokenAccount>,
+    pub user_ata_x: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
-        init,
+        init_if_needed,
         payer = user,
-        associated_token::mint = lp_token_mint,
+        associated_token::mint = mint_y,
+        associated_token::token_program = token_program_y,
         associated_token::authority = user
     )]
-    pub user_lp_ata: Account<'info, TokenAccount>,
+    pub user_ata_y: InterfaceAccount<'info, TokenAccount>,
 
     pub associated_token_program: Program<'info, AssociatedToken>,
-    pub token_program: Program<'info, Token>,
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> SwapTokens<'info> {
-    pub fn swap(&mut self, amount_in: u64, amount_out_min: u64, is_x: bool) -> Result<()> {
-        // calculating the swap fee (swap amount * fee in basis points / 10_000) //if 30 is bp, then 0.003 which is 0.3%
-        let swap_fee = amount_in
-            .checked_mul(self.pool.fee_bp as u64)
-            .unwrap()
-            .checked_div(10_000)
-            .unwrap();
-
-        match is_x {
-            true => self.pool.fee_collected_x += swap_fee,
-            false => self.pool.fee_collected_y += swap_fee,
-        }
+    pub fn swap(
+        &mut self,
+        amount_in: u64,
+        amount_out_min: u64,
+        is_x: bool,
+        wrap_unwrap_sol: bool,
+        deadline: Option<i64>,
+    ) -> Result<()> {
+        check_deadline(deadline)?;
+        require!(!self.pool.locked, PoolError::PoolIsLocked);
+        require!(!self.config.paused, PoolError::AmmPaused);
+        require!(amount_in > 0, PoolError::InvalidAmount);
+
+        self.pool.record(&self.observations, &Clock::get()?)?;
+
+        let (reserve_in, reserve_out) = match is_x {
+            true => (self.pool.reserve_x, self.pool.reserve_y),
+            false => (self.pool.reserve_y, self.pool.reserve_x),
+        };
+
+        let (mint_in, mint_out) = match is_x {
+            true => (self.mint_x.to_account_info(), self.mint_y.to_account_info()),
+            false => (self.mint_y.to_account_info(), self.mint_x.to_account_info()),
+        };
+
+        let amount_in_net = amount_in - get_transfer_fee(&mint_in, amount_in)?;
+        require!(amount_in_net > 0, PoolError::InvalidAmount);
+
+        // lp fee stays in the reserves, the protocol fee stays in the vault
+        // but is tracked as collectible
+        let protocol_fee = get_fee_amount(amount_in_net, self.pool.protocol_fee_bp)?;
+
+        let amount_out = get_amount_out(
+            amount_in_net,
+            reserve_in,
+            reserve_out,
+            self.pool.total_fee_bp(),
+        )?;
+
+        require!(amount_out > 0, PoolError::InsufficientLiquidity);
+
+        let amount_received = amount_out - get_transfer_fee(&mint_out, amount_out)?;
+        require!(
+            amount_received >= amount_out_min,
+            PoolError::SlippageExceeded
+        );
 
-        // amount after fee which we need to calc the amount of out token to the user
-        let amount_after_fee = amount_in.checked_sub(swap_fee).unwrap();
+        self.update_reserves(is_x, amount_in_net, protocol_fee, amount_out)?;
 
-        // vault balances
-        let vault_x = self.ata_x.amount;
-        let vault_y = self.ata_y.amount;
+        self.deposit_tokens_from_user(is_x, amount_in, wrap_unwrap_sol)?;
+        self.deposit_tokens_to_user(!is_x, amount_out, wrap_unwrap_sol)?;
 
-        // constant K = X * Y
-        let k = vault_x.checked_mul(vault_y).unwrap();
+        Ok(())
+    }
 
-        let new_vault_x = if is_x {
-            vault_x.checked_add(amount_after_fee).unwrap()
-        } else {
-            vault_x
+    // exact output swap: the user gets exactly `amount_out` and pays at most `amount_in_max`
+    pub fn swap_exact_out(
+        &mut self,
+        amount_out: u64,
+        amount_in_max: u64,
+        is_x: bool,
+        wrap_unwrap_sol: bool,
+        deadline: Option<i64>,
+    ) -> Result<()> {
+        check_deadline(deadline)?;
+        require!(!self.pool.locked, PoolError::PoolIsLocked);
+        require!(!self.config.paused, PoolError::AmmPaused);
+
+        self.pool.record(&self.observations, &Clock::get()?)?;
+
+        let (reserve_in, reserve_out) = match is_x {
+            true => (self.pool.reserve_x, self.pool.reserve_y),
+            false => (self.pool.reserve_y, self.pool.reserve_x),
         };
 
-        let new_vault_y = if !is_x {
-            vault_y.checked_add(amount_after_fee).unwrap()
-        } else {
-            vault_y
+        let (mint_in, mint_out) = match is_x {
+            true => (self.mint_x.to_account_info(), self.mint_y.to_account_info()),
+            false => (self.mint_y.to_account_info(), self.mint_x.to_account_info()),
         };
 
-        let amount_out = if is_x {
-            let new_vault_y = (k as u128).checked_div(new_vault_x as u128).unwrap() as u64;
+        let amount_out_gross = amount_out
+            .checked_add(get_transfer_inverse_fee(&mint_out, amount_out)?)
+            .ok_or(PoolError::OverFlowError)?;
 
-            let amount_out = vault_y.checked_sub(new_vault_y).unwrap();
+        // input the vault has to receive (swap fee included), rounded up in the pool's favour
+        let amount_in_net = get_amount_in(
+            amount_out_gross,
+            reserve_in,
+            reserve_out,
+            self.pool.total_fee_bp(),
+        )?;
 
-            amount_out
-        } else {
-            let new_vault_x = (k as u128).checked_div(new_vault_y as u128).unwrap() as u64;
+        let amount_in = amount_in_net
+            .checked_add(get_transfer_inverse_fee(&mint_in, amount_in_net)?)
+            .ok_or(PoolError::OverFlowError)?;
 
-            let amount_out = vault_x.checked_sub(new_vault_x).unwrap();
-            amount_out
-        };
+        require!(amount_in <= amount_in_max, PoolError::SlippageExceeded);
 
-        require!(amount_out >= amount_out_min, PoolError::SlippageExceeded);
+        // fee is charged on the input the vault receives, same as the exact input swap
+        let protocol_fee = get_fee_amount(amount_in_net, self.pool.protocol_fee_bp)?;
 
-        self.deposit_tokens_from_user(is_x, amount_in)?;
-        self.deposit_tokens_to_user(is_x, amount_out)?;
+        self.update_reserves(is_x, amount_in_net, protocol_fee, amount_out_gross)?;
+
+        self.deposit_tokens_from_user(is_x, amount_in, wrap_unwrap_sol)?;
+        self.deposit_tokens_to_user(!is_x, amount_out_gross, wrap_unwrap_sol)?;
 
         Ok(())
     }
 
-    fn deposit_tokens_from_user(&mut self, is_x: bool, amount: u64) -> Result<()> {
-        let (from, to) = match is_x {
+    // the curve prices against these tracked reserves instead of the vault balances, so
+    // donations and uncollected protocol fees never move the price. input (net of
+    // transfer fees) minus the protocol fee goes into the reserves, the output comes out
+    fn update_reserves(
+        &mut self,
+        is_x: bool,
+        amount_in: u64,
+        protocol_fee: u64,
+        amount_out: u64,
+    ) -> Result<()> {
+        let pool: &mut Pool = &mut self.pool;
+
+        let (reserve_in, reserve_out, fee_collected) = match is_x {
+            true => (
+                &mut pool.reserve_x,
+                &mut pool.reserve_y,
+                &mut pool.fee_collected_x,
+            ),
+            false => (
+                &mut pool.reserve_y,
+                &mut pool.reserve_x,
+                &mut pool.fee_collected_y,
+            ),
+        };
+
+        *fee_collected = fee_collected
+            .checked_add(protocol_fee)
+            .ok_or(PoolError::OverFlowError)?;
+        *reserve_in = reserve_in
+            .checked_add(amount_in - protocol_fee)
+            .ok_or(PoolError::OverFlowError)?;
+        *reserve_out = reserve_out
+            .checked_sub(amount_out)
+            .ok_or(PoolError::OutputExceedsReserves)?;
+
+        Ok(())
+    }
+
+    fn deposit_tokens_from_user(&mut self, is_x: bool, amount: u64, wrap: bool) -> Result<()> {
+        // when asked to, native sol is wrapped into the user's wsol account if it holds too little
+        if wrap {
+            match is_x {
+                true => wrap_sol(
+                    &self.user,
+                    &self.user_ata_x,
+                    amount,
+                    &self.system_program,
+                    &self.token_program_x,
+                )?,
+                false => wrap_sol(
+                    &self.user,
+                    &self.user_ata_y,
+                    amount,
+                    &self.system_program,
+                    &self.token_program_y,
+                )?,
+            }
+        }
+
+        let (from, to, mint, decimals, cpi_program) = match is_x {
             true => (
                 self.user_ata_x.to_account_info(),
                 self.ata_x.to_account_info(),
+                self.mint_x.to_account_info(),
+                self.mint_x.decimals,
+                self.token_program_x.to_account_info(),
             ),
             false => (
                 self.user_ata_y.to_account_info(),
                 self.ata_y.to_account_info(),
+                self.mint_y.to_account_info(),
+                self.mint_y.decimals,
+                self.token_program_y.to_account_info(),
             ),
         };
 
-        let cpi_program = self.token_program.to_account_info();
-
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from,
+            mint,
             to,
             authority: self.user.to_account_info(),
         };
 
         let ctx = CpiContext::new(cpi_program, cpi_accounts);
 
-        transfer(ctx, amount)?;
+        transfer_checked(ctx, amount, decimals)?;
 
         Ok(())
     }
 
-    fn deposit_tokens_to_user(&mut self, is_x: bool, amount: u64) -> Result<()> {
-        let (from, to) = match is_x {
+    fn deposit_tokens_to_user(&mut self, is_x: bool, amount: u64, unwrap: bool) -> Result<()> {
+        // when asked to, the wsol account is closed after the transfer and its whole
+        // balance comes back as native sol
+        let unwrap = unwrap
+            && match is_x {
+                true => self.user_ata_x.is_native(),
+                false => self.user_ata_y.is_native(),
+            };
+
+        let (from, to, mint, decimals, cpi_program) = match is_x {
             true => (
                 self.ata_x.to_account_info(),
                 self.user_ata_x.to_account_info(),
+                self.mint_x.to_account_info(),
+                self.mint_x.decimals,
+                self.token_program_x.to_account_info(),
             ),
             false => (
                 self.ata_y.to_account_info(),
                 self.user_ata_y.to_account_info(),
+                self.mint_y.to_account_info(),
+                self.mint_y.decimals,
+                self.token_program_y.to_account_info(),
             ),
         };
 
-        let auth_key = self.authority.key();
+        let mint_x = self.pool.mint_x;
+        let mint_y = self.pool.mint_y;
+        let fee_tier = self.pool.fee_tier.to_le_bytes();
 
         let seeds = &[
             POOL_SEED.as_bytes(),
-            auth_key.as_ref(),
+            mint_x.as_ref(),
+            mint_y.as_ref(),
+            fee_tier.as_ref(),
             &[self.pool.pool_bump],
         ];
 
         let signer_seeds = &[&seeds[..]];
 
-        let cpi_program = self.token_program.to_account_info();
-
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from,
+            mint,
             to,
             authority: self.pool.to_account_info(),
         };
 
         let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
 
-        transfer(ctx, amount)?;
+        transfer_checked(ctx, amount, decimals)?;
+
+        if unwrap {
+            match is_x {
+                true => unwrap_sol(&self.user, &self.user_ata_x, &self.token_program_x)?,
+                false => unwrap_sol(&self.user, &self.user_ata_y, &self.token_program_y)?,
+            }
+        }
 
         Ok(())
     }
@@ -0,0 +1,80 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    error::PoolError, AmmConfig, Observations, Pool, AMM_CONFIG_SEED, OBSERVATIONS_SEED, POOL_SEED,
+};
+
+// moves the reserves up to the vault balances (minus the uncollected protocol fees),
+// so tokens sent straight to the vaults are given to the LPs
+#[derive(Accounts)]
+pub struct SyncReserves<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mint::token_program = token_program_x)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_program_y)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        associated_token::mint = mint_x,
+        associated_token::token_program = token_program_x,
+        associated_token::authority = pool,
+    )]
+    pub ata_x: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        associated_token::mint = mint_y,
+        associated_token::token_program = token_program_y,
+        associated_token::authority = pool,
+    )]
+    pub ata_y: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [AMM_CONFIG_SEED.as_bytes()],
+        bump = config.bump
+    )]
+    pub config: Account<'info, AmmConfig>,
+
+    #[account(
+        mut,
+        has_one = authority,
+        has_one = mint_x,
+        has_one = mint_y,
+        seeds = [
+            POOL_SEED.as_bytes(),
+            pool.mint_x.as_ref(),
+            pool.mint_y.as_ref(),
+            &pool.fee_tier.to_le_bytes(),
+        ],
+        bump = pool.pool_bump
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+        mut,
+        seeds = [OBSERVATIONS_SEED.as_bytes(), pool.key().as_ref()],
+        bump = observations.load()?.bump
+    )]
+    pub observations: AccountLoader<'info, Observations>,
+
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
+}
+
+impl<'info> SyncReserves<'info> {
+    pub fn sync(&mut self) -> Result<()> {
+        require!(!self.config.paused, PoolError::AmmPaused);
+
+        self.pool.record(&self.observations, &Clock::get()?)?;
+
+        let reserve_x = self.ata_x.amount.saturating_sub(self.pool.fee_collected_x);
+        let reserve_y = self.ata_y.amount.saturating_sub(self.pool.fee_collected_y);
+
+        // reserves only ever move up, a vault can not hold less than the tracked reserve
+        self.pool.reserve_x = self.pool.reserve_x.max(reserve_x);
+        self.pool.reserve_y = self.pool.reserve_y.max(reserve_y);
+
+        Ok(())
+    }
+}
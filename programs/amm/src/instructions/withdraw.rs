@@ -1,99 +1,250 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{burn, transfer, Burn, Mint, Token, TokenAccount, Transfer},
+    token::Token,
+    token_interface::{
+        burn, transfer_checked, Burn, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
 };
 
-use crate::{error::PoolError, Pool, LP_TOKEN_MINT_SEED, POOL_SEED};
+use crate::{
+    error::PoolError,
+    helper::{
+        check_deadline, get_amount_out, get_fee_amount, get_transfer_fee, unwrap_sol, xy_from_l,
+    },
+    AmmConfig, Observations, Pool, AMM_CONFIG_SEED, LP_TOKEN_MINT_SEED, OBSERVATIONS_SEED,
+    POOL_SEED,
+};
 
 #[derive(Accounts)]
 pub struct Withdraw<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
-
     #[account(mut)]
     pub user: Signer<'info>,
 
-    pub mint_x: Account<'info, Mint>,
-    pub mint_y: Account<'info, Mint>,
+    #[account(mint::token_program = token_program_x)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_program_y)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         associated_token::mint = mint_x,
+        associated_token::token_program = token_program_x,
         associated_token::authority = pool,
     )]
-    pub ata_x: Account<'info, TokenAccount>,
+    pub ata_x: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         associated_token::mint = mint_y,
+        associated_token::token_program = token_program_y,
         associated_token::authority = pool,
     )]
-    pub ata_y: Account<'info, TokenAccount>,
+    pub ata_y: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         seeds = [LP_TOKEN_MINT_SEED.as_bytes(),pool.key().as_ref()],
         bump = pool.lp_bump
     )]
-    pub lp_token_mint: Account<'info, Mint>,
+    pub lp_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [AMM_CONFIG_SEED.as_bytes()],
+        bump = config.bump
+    )]
+    pub config: Account<'info, AmmConfig>,
 
     #[account(
         mut,
-        seeds = [POOL_SEED.as_bytes(),authority.key().as_ref()],
+        has_one = mint_x,
+        has_one = mint_y,
+        seeds = [
+            POOL_SEED.as_bytes(),
+            pool.mint_x.as_ref(),
+            pool.mint_y.as_ref(),
+            &pool.fee_tier.to_le_bytes(),
+        ],
         bump = pool.pool_bump
     )]
     pub pool: Account<'info, Pool>,
 
     #[account(
         mut,
+        seeds = [OBSERVATIONS_SEED.as_bytes(), pool.key().as_ref()],
+        bump = observations.load()?.bump
+    )]
+    pub observations: AccountLoader<'info, Observations>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
         associated_token::mint = mint_x,
+        associated_token::token_program = token_program_x,
         associated_token::authority = user
     )]
-    pub user_ata_x: Account<'info, TokenAccount>,
+    pub user_ata_x: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
-        mut,
+        init_if_needed,
+        payer = user,
         associated_token::mint = mint_y,
+        associated_token::token_program = token_program_y,
         associated_token::authority = user
     )]
-    pub user_ata_y: Account<'info, TokenAccount>,
+    pub user_ata_y: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         associated_token::mint = lp_token_mint,
+        associated_token::token_program = token_program,
         associated_token::authority = user
     )]
-    pub user_lp_ata: Account<'info, TokenAccount>,
+    pub user_lp_ata: InterfaceAccount<'info, TokenAccount>,
 
     pub associated_token_program: Program<'info, AssociatedToken>,
+    // lp mint is always a classic spl token mint
     pub token_program: Program<'info, Token>,
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> Withdraw<'info> {
-    pub fn withdraw(&mut self, amount: u64) -> Result<()> {
-        let total_lp_supply = self.pool.lp_supply;
+    // withdrawals are allowed even when the pool is locked or the amm is paused so LPs can always exit
+    pub fn withdraw(
+        &mut self,
+        amount: u64,
+        min_x_out: u64,
+        min_y_out: u64,
+        wrap_unwrap_sol: bool,
+        deadline: Option<i64>,
+    ) -> Result<()> {
+        check_deadline(deadline)?;
+
+        self.pool.record(&self.observations, &Clock::get()?)?;
+
+        let total_lp_supply = self.lp_token_mint.supply;
 
         require!(amount > 0, PoolError::InvalidAmount);
 
-        let return_x = (self.ata_x.amount as u128)
-            .checked_mul(amount as u128)
-            .ok_or(PoolError::OverFlowError)?
-            .checked_div(total_lp_supply as u128)
-            .ok_or(PoolError::OverFlowError)? as u64;
+        let vault_x = self.pool.reserve_x;
+        let vault_y = self.pool.reserve_y;
+
+        // same math the deposit uses to price lp tokens
+        let (return_x, return_y) = xy_from_l(total_lp_supply, vault_x, vault_y, amount)?;
+
+        let received_x = return_x - get_transfer_fee(&self.mint_x.to_account_info(), return_x)?;
+        let received_y = return_y - get_transfer_fee(&self.mint_y.to_account_info(), return_y)?;
 
-        let return_y = (self.ata_y.amount as u128)
-            .checked_mul(amount as u128)
-            .ok_or(PoolError::OverFlowError)?
-            .checked_div(total_lp_supply as u128)
-            .ok_or(PoolError::OverFlowError)? as u64;
+        require!(received_x >= min_x_out, PoolError::SlippageExceeded);
+        require!(received_y >= min_y_out, PoolError::SlippageExceeded);
 
         self.burn_tokens(amount)?;
-        self.withdraw_tokens(true, return_x)?;
-        self.withdraw_tokens(false, return_y)?;
+        self.withdraw_tokens(true, return_x, wrap_unwrap_sol)?;
+        self.withdraw_tokens(false, return_y, wrap_unwrap_sol)?;
+
+        self.pool.reserve_x = self
+            .pool
+            .reserve_x
+            .checked_sub(return_x)
+            .ok_or(PoolError::OutputExceedsReserves)?;
+        self.pool.reserve_y = self
+            .pool
+            .reserve_y
+            .checked_sub(return_y)
+            .ok_or(PoolError::OutputExceedsReserves)?;
+
+        Ok(())
+    }
+
+    // single sided withdrawal: burns lp, and swaps the unwanted side of the proportional
+    // share back into the pool so the user gets only one token. it swaps, so it is
+    // blocked while the pool is locked, the normal withdraw stays open
+    pub fn withdraw_single(
+        &mut self,
+        amount: u64,
+        want_x: bool,
+        min_out: u64,
+        wrap_unwrap_sol: bool,
+        deadline: Option<i64>,
+    ) -> Result<()> {
+        check_deadline(deadline)?;
+        require!(!self.pool.locked, PoolError::PoolIsLocked);
+        require!(!self.config.paused, PoolError::AmmPaused);
+        require!(amount > 0, PoolError::InvalidAmount);
+
+        self.pool.record(&self.observations, &Clock::get()?)?;
+
+        let total_lp_supply = self.lp_token_mint.supply;
+
+        let vault_x = self.pool.reserve_x;
+        let vault_y = self.pool.reserve_y;
 
-        self.pool.lp_supply -= amount;
+        let (return_x, return_y) = xy_from_l(total_lp_supply, vault_x, vault_y, amount)?;
+
+        // reserves once the proportional share is taken out, more than the lp supply
+        // would take out more than the reserves
+        let rest_x = vault_x
+            .checked_sub(return_x)
+            .ok_or(PoolError::OutputExceedsReserves)?;
+        let rest_y = vault_y
+            .checked_sub(return_y)
+            .ok_or(PoolError::OutputExceedsReserves)?;
+
+        let (reserve_in, reserve_out, swap_amount, amount_kept) = match want_x {
+            true => (rest_y, rest_x, return_y, return_x),
+            false => (rest_x, rest_y, return_x, return_y),
+        };
+
+        let swap_out = get_amount_out(
+            swap_amount,
+            reserve_in,
+            reserve_out,
+            self.pool.total_fee_bp(),
+        )?;
+
+        let protocol_fee = get_fee_amount(swap_amount, self.pool.protocol_fee_bp)?;
+
+        let amount_out = amount_kept
+            .checked_add(swap_out)
+            .ok_or(PoolError::OverFlowError)?;
+
+        let mint_out = match want_x {
+            true => self.mint_x.to_account_info(),
+            false => self.mint_y.to_account_info(),
+        };
+
+        let amount_received = amount_out - get_transfer_fee(&mint_out, amount_out)?;
+        require!(amount_received >= min_out, PoolError::SlippageExceeded);
+
+        // the swapped side never leaves the vault, only its protocol fee is set aside
+        let pool: &mut Pool = &mut self.pool;
+        let (reserve_kept, reserve_swapped, fee_collected) = match want_x {
+            true => (
+                &mut pool.reserve_x,
+                &mut pool.reserve_y,
+                &mut pool.fee_collected_y,
+            ),
+            false => (
+                &mut pool.reserve_y,
+                &mut pool.reserve_x,
+                &mut pool.fee_collected_x,
+            ),
+        };
+
+        *fee_collected = fee_collected
+            .checked_add(protocol_fee)
+            .ok_or(PoolError::OverFlowError)?;
+        *reserve_swapped = reserve_swapped
+            .checked_sub(protocol_fee)
+            .ok_or(PoolError::OutputExceedsReserves)?;
+        *reserve_kept = reserve_kept
+            .checked_sub(amount_out)
+            .ok_or(PoolError::OutputExceedsReserves)?;
+
+        self.burn_tokens(amount)?;
+        self.withdraw_tokens(want_x, amount_out, wrap_unwrap_sol)?;
 
         Ok(())
     }
@@ -114,39 +265,63 @@ impl<'info> Withdraw<'info> {
         Ok(())
     }
 
-    pub fn withdraw_tokens(&mut self, is_x: bool, amount: u64) -> Result<()> {
-        let (from, to) = match is_x {
+    pub fn withdraw_tokens(&mut self, is_x: bool, amount: u64, unwrap: bool) -> Result<()> {
+        // when asked to, the wsol account is closed after the transfer and its whole
+        // balance comes back as native sol
+        let unwrap = unwrap
+            && match is_x {
+                true => self.user_ata_x.is_native(),
+                false => self.user_ata_y.is_native(),
+            };
+
+        let (from, to, mint, decimals, cpi_program) = match is_x {
             true => (
                 self.ata_x.to_account_info(),
                 self.user_ata_x.to_account_info(),
+                self.mint_x.to_account_info(),
+                self.mint_x.decimals,
+                self.token_program_x.to_account_info(),
             ),
             false => (
                 self.ata_y.to_account_info(),
                 self.user_ata_y.to_account_info(),
+                self.mint_y.to_account_info(),
+                self.mint_y.decimals,
+                self.token_program_y.to_account_info(),
             ),
         };
 
-        let auth_key = self.authority.key();
+        let mint_x = self.pool.mint_x;
+        let mint_y = self.pool.mint_y;
+        let fee_tier = self.pool.fee_tier.to_le_bytes();
 
         let seeds = &[
             POOL_SEED.as_bytes(),
-            auth_key.as_ref(),
+            mint_x.as_ref(),
+            mint_y.as_ref(),
+            fee_tier.as_ref(),
             &[self.pool.pool_bump],
         ];
 
         let signer_seeds = &[&seeds[..]];
 
-        let cpi_program = self.token_program.to_account_info();
-
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from,
+            mint,
             to,
             authority: self.pool.to_account_info(),
         };
 
         let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
 
-        transfer(cpi_ctx, amount)?;
+        transfer_checked(cpi_ctx, amount, decimals)?;
+
+        if unwrap {
+            match is_x {
+                true => unwrap_sol(&self.user, &self.user_ata_x, &self.token_program_x)?,
+                false => unwrap_sol(&self.user, &self.user_ata_y, &self.token_program_y)?,
+            }
+        }
 
         Ok(())
     }
@@ -1,5 +1,6 @@
 pub mod constants;
 pub mod error;
+pub mod events;
 pub mod helper;
 pub mod instructions;
 pub mod state;
@@ -17,16 +18,64 @@ pub mod amm {
 
     use super::*;
 
-    pub fn initialize_pool(ctx: Context<Initialize>, fee: u16, lp_supply: u64) -> Result<()> {
-        ctx.accounts.initialize_pool(fee, lp_supply, ctx.bumps)?;
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        fee_tiers: Vec<u16>,
+        protocol_fee_share_bp: u16,
+        treasury: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts
+            .initialize_config(fee_tiers, protocol_fee_share_bp, treasury, ctx.bumps)?;
+        Ok(())
+    }
+
+    pub fn update_config(
+        ctx: Context<ConfigOps>,
+        fee_tiers: Vec<u16>,
+        protocol_fee_share_bp: u16,
+        treasury: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts
+            .update_config(fee_tiers, protocol_fee_share_bp, treasury)?;
+        Ok(())
+    }
+
+    pub fn set_paused(ctx: Context<ConfigOps>, paused: bool) -> Result<()> {
+        ctx.accounts.set_paused(paused)?;
+        Ok(())
+    }
+
+    pub fn initialize_pool(ctx: Context<Initialize>, fee_tier_index: u8) -> Result<()> {
+        ctx.accounts.initialize_pool(fee_tier_index, ctx.bumps)?;
         Ok(())
     }
     pub fn provide_liquidity(
         ctx: Context<ProvideLiquidity>,
         max_x_token: u64,
         max_y_token: u64,
+        min_lp_out: u64,
+        wrap_unwrap_sol: bool,
+        deadline: Option<i64>,
     ) -> Result<()> {
-        ctx.accounts.deposit_tokens(max_x_token, max_y_token)?;
+        ctx.accounts.deposit_tokens(
+            max_x_token,
+            max_y_token,
+            min_lp_out,
+            wrap_unwrap_sol,
+            deadline,
+        )?;
+        Ok(())
+    }
+    pub fn provide_liquidity_single(
+        ctx: Context<ProvideLiquidity>,
+        amount_in: u64,
+        is_x: bool,
+        min_lp_out: u64,
+        wrap_unwrap_sol: bool,
+        deadline: Option<i64>,
+    ) -> Result<()> {
+        ctx.accounts
+            .deposit_single(amount_in, is_x, min_lp_out, wrap_unwrap_sol, deadline)?;
         Ok(())
     }
     pub fn swap(
@@ -34,13 +83,108 @@ pub mod amm {
         amount_in: u64,
         amount_out_min: u64,
         is_x: bool,
+        wrap_unwrap_sol: bool,
+        deadline: Option<i64>,
+    ) -> Result<()> {
+        ctx.accounts
+            .swap(amount_in, amount_out_min, is_x, wrap_unwrap_sol, deadline)?;
+        Ok(())
+    }
+
+    pub fn swap_exact_out(
+        ctx: Context<SwapTokens>,
+        amount_out: u64,
+        amount_in_max: u64,
+        is_x: bool,
+        wrap_unwrap_sol: bool,
+        deadline: Option<i64>,
+    ) -> Result<()> {
+        ctx.accounts
+            .swap_exact_out(amount_out, amount_in_max, is_x, wrap_unwrap_sol, deadline)?;
+        Ok(())
+    }
+
+    pub fn withdraw(
+        ctx: Context<Withdraw>,
+        amount: u64,
+        min_x_out: u64,
+        min_y_out: u64,
+        wrap_unwrap_sol: bool,
+        deadline: Option<i64>,
+    ) -> Result<()> {
+        ctx.accounts
+            .withdraw(amount, min_x_out, min_y_out, wrap_unwrap_sol, deadline)?;
+        Ok(())
+    }
+
+    pub fn withdraw_single(
+        ctx: Context<Withdraw>,
+        amount: u64,
+        want_x: bool,
+        min_out: u64,
+        wrap_unwrap_sol: bool,
+        deadline: Option<i64>,
     ) -> Result<()> {
-        ctx.accounts.swap(amount_in, amount_out_min, is_x)?;
+        ctx.accounts
+            .withdraw_single(amount, want_x, min_out, wrap_unwrap_sol, deadline)?;
+        Ok(())
+    }
+
+    pub fn set_fee(ctx: Context<PoolOps>, lp_fee_bp: u16, protocol_fee_bp: u16) -> Result<()> {
+        ctx.accounts.set_fee(lp_fee_bp, protocol_fee_bp)?;
+        Ok(())
+    }
+
+    pub fn update_fee(ctx: Context<UpdateFee>) -> Result<()> {
+        ctx.accounts.update_fee()?;
+        Ok(())
+    }
+
+    pub fn lock_pool(ctx: Context<PoolOps>) -> Result<()> {
+        ctx.accounts.lock_pool()?;
+        Ok(())
+    }
+
+    pub fn unlock_pool(ctx: Context<PoolOps>) -> Result<()> {
+        ctx.accounts.unlock_pool()?;
+        Ok(())
+    }
+
+    pub fn collect_protocol_fees(ctx: Context<CollectProtocolFees>) -> Result<()> {
+        ctx.accounts.collect_protocol_fees()?;
+        Ok(())
+    }
+
+    pub fn skim(ctx: Context<Skim>) -> Result<()> {
+        ctx.accounts.skim()?;
+        Ok(())
+    }
+
+    pub fn sync(ctx: Context<SyncReserves>) -> Result<()> {
+        ctx.accounts.sync()?;
+        Ok(())
+    }
+
+    pub fn increase_observation_cardinality(
+        ctx: Context<IncreaseObservationCardinality>,
+        cardinality_next: u16,
+    ) -> Result<()> {
+        ctx.accounts
+            .increase_observation_cardinality(cardinality_next)?;
+        Ok(())
+    }
+
+    pub fn observe(ctx: Context<Observe>, seconds_ago: Vec<u32>) -> Result<Vec<PriceObservation>> {
+        ctx.accounts.observe(seconds_ago)
+    }
+
+    pub fn propose_authority(ctx: Context<PoolOps>, new_authority: Pubkey) -> Result<()> {
+        ctx.accounts.propose_authority(new_authority)?;
         Ok(())
     }
 
-    pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
-        ctx.accounts.withdraw(amount)?;
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        ctx.accounts.accept_authority()?;
         Ok(())
     }
 }
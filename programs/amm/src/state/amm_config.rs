@@ -0,0 +1,24 @@
+use anchor_lang::prelude::*;
+
+use crate::MAX_FEE_TIERS;
+
+// program wide settings, a single PDA created once by the program upgrade authority
+#[account]
+#[derive(InitSpace)]
+pub struct AmmConfig {
+    pub admin: Pubkey,
+
+    // allowed total swap fees in basis points, pools pick one by index
+    #[max_len(MAX_FEE_TIERS)]
+    pub fee_tiers: Vec<u16>,
+
+    // share of every fee tier that goes to the protocol, in bp of the fee
+    pub protocol_fee_share_bp: u16,
+    pub treasury: Pubkey,
+
+    // blocks everything that moves reserves or fees on every pool, plain withdrawals
+    // stay open so LPs can always exit
+    pub paused: bool,
+
+    pub bump: u8,
+}
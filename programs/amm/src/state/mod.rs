@@ -1,2 +1,8 @@
 pub mod pool;
 pub use pool::*;
+
+pub mod amm_config;
+pub use amm_config::*;
+
+pub mod observations;
+pub use observations::*;
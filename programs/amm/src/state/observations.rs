@@ -0,0 +1,208 @@
+use std::cell::{Ref, RefMut};
+
+use anchor_lang::{__private::bytemuck, prelude::*};
+
+use crate::{error::PoolError, Pool, PriceObservation};
+
+// snapshot of the pool's price accumulators, a zero timestamp marks an unused slot.
+// packed so the u128 fields do not depend on the account data alignment
+#[zero_copy(unsafe)]
+#[repr(C, packed)]
+#[derive(Default, Debug)]
+pub struct Observation {
+    pub price_x_cumulative: u128,
+    pub price_y_cumulative: u128,
+    pub timestamp: i64,
+    pub slot: u64,
+}
+
+// zero_copy(unsafe) leaves these out, the slots are cast straight from the account data.
+// the struct is packed without padding and every bit pattern is a valid observation
+unsafe impl bytemuck::Pod for Observation {}
+unsafe impl bytemuck::Zeroable for Observation {}
+
+impl From<Observation> for PriceObservation {
+    fn from(observation: Observation) -> Self {
+        PriceObservation {
+            price_x_cumulative: observation.price_x_cumulative,
+            price_y_cumulative: observation.price_y_cumulative,
+            timestamp: observation.timestamp,
+        }
+    }
+}
+
+// ring buffer of price observations for a pool, in the style of the uniswap v3 oracle.
+// `cardinality` slots are in use, it grows to `cardinality_next` once the writes wrap
+// around to the end of the used part of the buffer. this is only the header, the slots
+// follow it in the account data so the account holds exactly `cardinality_next` of them
+// and grows (paid by whoever asks for more) with `increase_observation_cardinality`
+#[account(zero_copy(unsafe))]
+#[repr(C, packed)]
+pub struct Observations {
+    pub pool: Pubkey,
+    // slot of the most recent observation
+    pub index: u16,
+    pub cardinality: u16,
+    pub cardinality_next: u16,
+    pub bump: u8,
+}
+
+impl Observations {
+    const HEADER_SPACE: usize = 8 + std::mem::size_of::<Observations>();
+
+    // account size for the header and `cardinality` observation slots
+    pub fn space(cardinality: u16) -> usize {
+        Self::HEADER_SPACE + cardinality as usize * std::mem::size_of::<Observation>()
+    }
+
+    // header and observation slots of an observations account, the discriminator is
+    // checked when the account loader is deserialized
+    pub fn load_buffer<'a>(
+        observations: &'a AccountLoader<Observations>,
+    ) -> Result<(Ref<'a, Observations>, Ref<'a, [Observation]>)> {
+        let data = observations.as_ref().try_borrow_data()?;
+
+        Ok(Ref::map_split(data, |data| {
+            let (header, slots) = data.split_at(Self::HEADER_SPACE);
+            (
+                bytemuck::from_bytes(&header[8..]),
+                bytemuck::cast_slice(slots),
+            )
+        }))
+    }
+
+    pub fn load_buffer_mut<'a>(
+        observations: &'a AccountLoader<Observations>,
+    ) -> Result<(RefMut<'a, Observations>, RefMut<'a, [Observation]>)> {
+        let data = observations.as_ref().try_borrow_mut_data()?;
+
+        Ok(RefMut::map_split(data, |data| {
+            let (header, slots) = data.split_at_mut(Self::HEADER_SPACE);
+            (
+                bytemuck::from_bytes_mut(&mut header[8..]),
+                bytemuck::cast_slice_mut(slots),
+            )
+        }))
+    }
+
+    // records the pool's accumulators, at most once per slot. has to run right after
+    // the accumulators are updated and before the reserves change
+    pub fn write(&mut self, observations: &mut [Observation], pool: &Pool, slot: u64) {
+        if observations[self.index as usize].slot == slot {
+            return;
+        }
+
+        if self.cardinality_next > self.cardinality && self.index == self.cardinality - 1 {
+            self.cardinality = self.cardinality_next;
+        }
+
+        self.index = (self.index + 1) % self.cardinality;
+        observations[self.index as usize] = Observation {
+            price_x_cumulative: pool.price_x_cumulative,
+            price_y_cumulative: pool.price_y_cumulative,
+            timestamp: pool.last_update_timestamp,
+            slot,
+        };
+    }
+
+    // accumulators `seconds_ago` before `now`. past the newest observation the pool's
+    // own accumulators are extended, in between two observations the value is interpolated
+    pub fn observe(
+        &self,
+        observations: &[Observation],
+        pool: &Pool,
+        now: i64,
+        seconds_ago: u32,
+    ) -> Result<PriceObservation> {
+        let target = now - seconds_ago as i64;
+
+        // the newest observation is the pool's last update, the reserves have not
+        // changed since, so the pool can extend it to any later time
+        let newest = observations[self.index as usize];
+        if target >= newest.timestamp {
+            return Ok(pool.observe(target));
+        }
+
+        require!(
+            target >= self.oldest(observations).timestamp,
+            PoolError::ObservationTooOld
+        );
+
+        let (before, after) = self.binary_search(observations, target);
+
+        if target == before.timestamp {
+            return Ok(before.into());
+        }
+        if target == after.timestamp {
+            return Ok(after.into());
+        }
+
+        // the price was constant between the two observations
+        let observation_elapsed = (after.timestamp - before.timestamp) as u128;
+        let target_elapsed = (target - before.timestamp) as u128;
+
+        Ok(PriceObservation {
+            price_x_cumulative: before.price_x_cumulative.wrapping_add(
+                after
+                    .price_x_cumulative
+                    .wrapping_sub(before.price_x_cumulative)
+                    / observation_elapsed
+                    * target_elapsed,
+            ),
+            price_y_cumulative: before.price_y_cumulative.wrapping_add(
+                after
+                    .price_y_cumulative
+                    .wrapping_sub(before.price_y_cumulative)
+                    / observation_elapsed
+                    * target_elapsed,
+            ),
+            timestamp: target,
+        })
+    }
+
+    // the slot after the newest one, unless the buffer has not wrapped around yet
+    fn oldest(&self, observations: &[Observation]) -> Observation {
+        let next = observations[((self.index + 1) % self.cardinality) as usize];
+
+        match next.timestamp == 0 {
+            true => observations[0],
+            false => next,
+        }
+    }
+
+    // observations right at or before and right after `target`, which has to lie
+    // between the oldest and the newest observation
+    fn binary_search(
+        &self,
+        observations: &[Observation],
+        target: i64,
+    ) -> (Observation, Observation) {
+        let cardinality = self.cardinality as usize;
+
+        let mut left = self.index as usize + 1;
+        let mut right = left + cardinality - 1;
+
+        loop {
+            let i = (left + right) / 2;
+
+            let before = observations[i % cardinality];
+
+            // unused slot, the oldest observations are further right
+            if before.timestamp == 0 {
+                left = i + 1;
+                continue;
+            }
+
+            let after = observations[(i + 1) % cardinality];
+
+            if before.timestamp <= target {
+                if target <= after.timestamp {
+                    return (before, after);
+                }
+                left = i + 1;
+            } else {
+                right = i - 1;
+            }
+        }
+    }
+}
@@ -1,26 +1,111 @@
 use anchor_lang::prelude::*;
 
+use crate::{helper::get_price_uq64_64, Observations};
+
 #[account]
 #[derive(InitSpace)]
 pub struct Pool {
     pub authority: Pubkey,
+    pub pending_authority: Option<Pubkey>,
 
+    // the pool PDA is derived from the sorted mint pair and the fee tier
     pub mint_x: Pubkey,
     pub mint_y: Pubkey,
+    pub fee_tier: u16,
 
     pub ata_x: Pubkey,
     pub ata_y: Pubkey,
 
+    // lp supply is always read from the lp mint, it is the single source of truth
     pub lp_mint: Pubkey,
-    pub lp_supply: u64,
 
-    pub fee_bp: u16,
+    // fee kept in the reserves for the LPs
+    pub lp_fee_bp: u16,
+    // fee set aside for the protocol, collectible by the config treasury
+    pub protocol_fee_bp: u16,
+
+    // reserves used by the curve, tracked instead of read from the vault balances so
+    // tokens sent straight to the vaults can not move the price
+    pub reserve_x: u64,
+    pub reserve_y: u64,
 
+    // protocol fees sitting in the vaults that are not part of the reserves
     pub fee_collected_x: u64,
     pub fee_collected_y: u64,
 
+    // time weighted price accumulators in UQ64.64 fixed point, price_x is y per x and
+    // price_y is x per y. they wrap on overflow, only differences are meaningful
+    pub price_x_cumulative: u128,
+    pub price_y_cumulative: u128,
+    pub last_update_timestamp: i64,
+
     pub pool_bump: u8,
     pub lp_bump: u8,
 
     pub locked: bool,
 }
+
+impl Pool {
+    // lp + protocol fee, validated against MAX_FEE_BP whenever it is set
+    pub fn total_fee_bp(&self) -> u16 {
+        self.lp_fee_bp + self.protocol_fee_bp
+    }
+
+    // adds the price that held since the last update to the accumulators,
+    // has to run before the reserves change
+    pub fn update_price_cumulative(&mut self, now: i64) {
+        let observation = self.observe(now);
+
+        self.price_x_cumulative = observation.price_x_cumulative;
+        self.price_y_cumulative = observation.price_y_cumulative;
+        self.last_update_timestamp = now;
+    }
+
+    // accumulates the price that held up to now and writes it to the observation buffer,
+    // every instruction that moves the reserves calls this first
+    pub fn record(
+        &mut self,
+        observations: &AccountLoader<Observations>,
+        clock: &Clock,
+    ) -> Result<()> {
+        self.update_price_cumulative(clock.unix_timestamp);
+
+        let (mut header, mut slots) = Observations::load_buffer_mut(observations)?;
+        header.write(&mut slots, self, clock.slot);
+
+        Ok(())
+    }
+
+    // accumulators as they would be if updated at `now`, so readers get an up to date
+    // value even when the pool has not been touched for a while
+    pub fn observe(&self, now: i64) -> PriceObservation {
+        let mut observation = PriceObservation {
+            price_x_cumulative: self.price_x_cumulative,
+            price_y_cumulative: self.price_y_cumulative,
+            timestamp: now,
+        };
+
+        let elapsed = now.saturating_sub(self.last_update_timestamp);
+
+        if elapsed > 0 && self.reserve_x > 0 && self.reserve_y > 0 {
+            let elapsed = elapsed as u128;
+
+            observation.price_x_cumulative = observation.price_x_cumulative.wrapping_add(
+                get_price_uq64_64(self.reserve_y, self.reserve_x).wrapping_mul(elapsed),
+            );
+            observation.price_y_cumulative = observation.price_y_cumulative.wrapping_add(
+                get_price_uq64_64(self.reserve_x, self.reserve_y).wrapping_mul(elapsed),
+            );
+        }
+
+        observation
+    }
+}
+
+// snapshot of the price accumulators, two of them give the TWAP in between
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct PriceObservation {
+    pub price_x_cumulative: u128,
+    pub price_y_cumulative: u128,
+    pub timestamp: i64,
+}
@@ -1,16 +1,2057 @@
 import * as anchor from "@coral-xyz/anchor";
-import { Program } from "@coral-xyz/anchor";
+import { Program, BN } from "@coral-xyz/anchor";
+import {
+  createInitializeMintInstruction,
+  createInitializePermanentDelegateInstruction,
+  createInitializeTransferFeeConfigInstruction,
+  createMint,
+  ExtensionType,
+  getAssociatedTokenAddressSync,
+  getMint,
+  getMintLen,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+  NATIVE_MINT,
+  TOKEN_2022_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+  transfer,
+} from "@solana/spl-token";
+import {
+  Keypair,
+  PublicKey,
+  sendAndConfirmTransaction,
+  SystemProgram,
+  Transaction,
+} from "@solana/web3.js";
+import { assert } from "chai";
 import { Amm } from "../target/types/amm";
 
+const POOL_SEED = "amm_pool";
+const LP_TOKEN_MINT_SEED = "lp_mint";
+const AMM_CONFIG_SEED = "amm_config";
+const OBSERVATIONS_SEED = "observations";
+const OBSERVATION_CAPACITY = 200;
+// discriminator + pool, index, cardinality, cardinality_next and bump
+const OBSERVATIONS_HEADER_SPACE = 8 + 32 + 2 + 2 + 2 + 1;
+// two u128 accumulators, the i64 timestamp and the u64 slot
+const OBSERVATION_SPACE = 16 + 16 + 8 + 8;
+const MINIMUM_LIQUIDITY = 1_000;
+const MAX_FEE_BP = 1_000;
+const FEE_TIERS = [5, 30, 100];
+const FEE_TIER_INDEX = 1;
+const FEE_TIER = FEE_TIERS[FEE_TIER_INDEX];
+// 20% of every fee tier goes to the protocol
+const PROTOCOL_FEE_SHARE_BP = 2_000;
+const BPF_LOADER_UPGRADEABLE = new PublicKey(
+  "BPFLoaderUpgradeab1e11111111111111111111111"
+);
+
 describe("amm", () => {
   // Configure the client to use the local cluster.
-  anchor.setProvider(anchor.AnchorProvider.env());
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
 
   const program = anchor.workspace.amm as Program<Amm>;
+  const connection = provider.connection;
+
+  const authority = (provider.wallet as anchor.Wallet).payer;
+  const user = Keypair.generate();
+
+  let mintX: PublicKey;
+  let mintY: PublicKey;
+  let pool: PublicKey;
+  let lpTokenMint: PublicKey;
+  let ataX: PublicKey;
+  let ataY: PublicKey;
+  let lockedLpAta: PublicKey;
+  let observations: PublicKey;
+  let userAtaX: PublicKey;
+  let userAtaY: PublicKey;
+  let userLpAta: PublicKey;
+
+  const poolFor = (x: PublicKey, y: PublicKey) => {
+    const feeTier = Buffer.alloc(2);
+    feeTier.writeUInt16LE(FEE_TIER);
+
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from(POOL_SEED), x.toBuffer(), y.toBuffer(), feeTier],
+      program.programId
+    )[0];
+  };
+
+  // works for both token programs
+  const balance = async (address: PublicKey) =>
+    Number((await connection.getTokenAccountBalance(address)).value.amount);
+
+  const lpSupply = async () =>
+    Number((await getMint(connection, lpTokenMint)).supply);
+
+  // the observation slots follow the header and are not part of the idl account
+  const readObservations = async (address: PublicKey) => {
+    const { data } = await connection.getAccountInfo(address);
+    const slots = [];
+
+    for (
+      let offset = OBSERVATIONS_HEADER_SPACE;
+      offset < data.length;
+      offset += OBSERVATION_SPACE
+    ) {
+      slots.push({
+        priceXCumulative: new BN(data.subarray(offset, offset + 16), "le"),
+        priceYCumulative: new BN(data.subarray(offset + 16, offset + 32), "le"),
+        timestamp: new BN(data.subarray(offset + 32, offset + 40), "le"),
+      });
+    }
+
+    return slots;
+  };
+
+  const reservesOf = async (address: PublicKey) => {
+    const state = await program.account.pool.fetch(address);
+    return [state.reserveX.toNumber(), state.reserveY.toNumber()];
+  };
+
+  // same rounding as `get_amount_out`
+  const amountOut = (
+    amountIn: number,
+    reserveIn: number,
+    reserveOut: number
+  ) => {
+    const afterFee = Math.floor((amountIn * (10_000 - FEE_TIER)) / 10_000);
+    const newReserveOut = Math.ceil(
+      (reserveIn * reserveOut) / (reserveIn + afterFee)
+    );
+    return reserveOut - newReserveOut;
+  };
+
+  // same rounding as `get_amount_in`, both steps round up
+  const amountIn = (
+    amountOut: number,
+    reserveIn: number,
+    reserveOut: number
+  ) => {
+    const afterFee = Math.ceil(
+      (reserveIn * amountOut) / (reserveOut - amountOut)
+    );
+    return Math.ceil((afterFee * 10_000) / (10_000 - FEE_TIER));
+  };
+
+  // two new classic mints, sorted the way pools are keyed
+  const createMintPair = async () =>
+    (
+      await Promise.all(
+        [0, 1].map(() =>
+          createMint(connection, authority, authority.publicKey, null, 6)
+        )
+      )
+    ).sort((a, b) => Buffer.compare(a.toBuffer(), b.toBuffer()));
+
+  // fresh pool over two new classic mints, the user gets `amount` of each
+  const createPool = async (amount: number) => {
+    const [x, y] = await createMintPair();
+
+    const newPool = poolFor(x, y);
+    const [newLpMint] = PublicKey.findProgramAddressSync(
+      [Buffer.from(LP_TOKEN_MINT_SEED), newPool.toBuffer()],
+      program.programId
+    );
+
+    const accounts: { [name: string]: PublicKey } = {
+      mintX: x,
+      mintY: y,
+      tokenProgramX: TOKEN_PROGRAM_ID,
+      tokenProgramY: TOKEN_PROGRAM_ID,
+      ataX: getAssociatedTokenAddressSync(x, newPool, true),
+      ataY: getAssociatedTokenAddressSync(y, newPool, true),
+      lpTokenMint: newLpMint,
+      pool: newPool,
+      userAtaX: getAssociatedTokenAddressSync(x, user.publicKey),
+      userAtaY: getAssociatedTokenAddressSync(y, user.publicKey),
+      userLpAta: getAssociatedTokenAddressSync(newLpMint, user.publicKey),
+      lockedLpAta: getAssociatedTokenAddressSync(newLpMint, newPool, true),
+    };
+
+    for (const mint of [x, y]) {
+      const ata = await getOrCreateAssociatedTokenAccount(
+        connection,
+        user,
+        mint,
+        user.publicKey
+      );
+      await mintTo(connection, authority, mint, ata.address, authority, amount);
+    }
+
+    await program.methods
+      .initializePool(FEE_TIER_INDEX)
+      .accountsPartial({ authority: authority.publicKey, ...accounts })
+      .rpc();
+
+    return accounts;
+  };
+
+  before(async () => {
+    await connection.confirmTransaction(
+      await connection.requestAirdrop(user.publicKey, 10e9),
+      "confirmed"
+    );
+
+    const mintA = await createMint(
+      connection,
+      authority,
+      authority.publicKey,
+      null,
+      6
+    );
+    const mintB = await createMint(
+      connection,
+      authority,
+      authority.publicKey,
+      null,
+      6
+    );
+
+    // pools are keyed by the sorted mint pair and the fee tier
+    [mintX, mintY] = [mintA, mintB].sort((a, b) =>
+      Buffer.compare(a.toBuffer(), b.toBuffer())
+    );
+
+    pool = poolFor(mintX, mintY);
+    [observations] = PublicKey.findProgramAddressSync(
+      [Buffer.from(OBSERVATIONS_SEED), pool.toBuffer()],
+      program.programId
+    );
+    [lpTokenMint] = PublicKey.findProgramAddressSync(
+      [Buffer.from(LP_TOKEN_MINT_SEED), pool.toBuffer()],
+      program.programId
+    );
+
+    ataX = getAssociatedTokenAddressSync(mintX, pool, true);
+    ataY = getAssociatedTokenAddressSync(mintY, pool, true);
+    lockedLpAta = getAssociatedTokenAddressSync(lpTokenMint, pool, true);
+    userLpAta = getAssociatedTokenAddressSync(lpTokenMint, user.publicKey);
+
+    userAtaX = (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        user,
+        mintX,
+        user.publicKey
+      )
+    ).address;
+    userAtaY = (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        user,
+        mintY,
+        user.publicKey
+      )
+    ).address;
+
+    await mintTo(
+      connection,
+      authority,
+      mintX,
+      userAtaX,
+      authority,
+      1_000_000_000
+    );
+    await mintTo(
+      connection,
+      authority,
+      mintY,
+      userAtaY,
+      authority,
+      1_000_000_000
+    );
+  });
+
+  it("initializes the amm config", async () => {
+    // only the program upgrade authority can create the config
+    const [programData] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      BPF_LOADER_UPGRADEABLE
+    );
+
+    await program.methods
+      .initializeConfig(FEE_TIERS, PROTOCOL_FEE_SHARE_BP, authority.publicKey)
+      .accountsPartial({
+        admin: authority.publicKey,
+        programData,
+      })
+      .rpc();
+
+    const [config] = PublicKey.findProgramAddressSync(
+      [Buffer.from(AMM_CONFIG_SEED)],
+      program.programId
+    );
+    const state = await program.account.ammConfig.fetch(config);
+    assert.ok(state.admin.equals(authority.publicKey));
+    assert.deepEqual(state.feeTiers, FEE_TIERS);
+    assert.equal(state.protocolFeeShareBp, PROTOCOL_FEE_SHARE_BP);
+    assert.isFalse(state.paused);
+  });
+
+  it("initializes the pool", async () => {
+    await program.methods
+      .initializePool(FEE_TIER_INDEX)
+      .accountsPartial({
+        authority: authority.publicKey,
+        mintX,
+        mintY,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        ataX,
+        ataY,
+        lpTokenMint,
+        pool,
+        lockedLpAta,
+      })
+      .rpc();
+
+    const state = await program.account.pool.fetch(pool);
+    assert.ok(state.authority.equals(authority.publicKey));
+    assert.ok(state.lpMint.equals(lpTokenMint));
+    assert.equal(state.feeTier, FEE_TIER);
+    // 30 * 2_000 / 10_000 = 6
+    assert.equal(state.lpFeeBp, 24);
+    assert.equal(state.protocolFeeBp, 6);
+    assert.equal(await lpSupply(), 0);
+  });
+
+  it("grows the observation buffer", async () => {
+    let state = await program.account.observations.fetch(observations);
+    assert.equal(state.cardinality, 1);
+
+    // pool creation only paid for a single slot
+    let info = await connection.getAccountInfo(observations);
+    assert.equal(
+      info.data.length,
+      OBSERVATIONS_HEADER_SPACE + OBSERVATION_SPACE
+    );
+
+    // anyone can grow it, the caller pays the rent for the new slots
+    const rentBefore = info.lamports;
+    const lamportsBefore = await connection.getBalance(user.publicKey);
+
+    await program.methods
+      .increaseObservationCardinality(10)
+      .accountsPartial({ payer: user.publicKey, pool })
+      .signers([user])
+      .rpc();
+
+    state = await program.account.observations.fetch(observations);
+    assert.equal(state.cardinality, 1);
+    assert.equal(state.cardinalityNext, 10);
+
+    info = await connection.getAccountInfo(observations);
+    assert.equal(
+      info.data.length,
+      OBSERVATIONS_HEADER_SPACE + 10 * OBSERVATION_SPACE
+    );
+    const rent = await connection.getMinimumBalanceForRentExemption(
+      info.data.length
+    );
+    assert.equal(info.lamports, rent);
+    assert.isAtMost(
+      await connection.getBalance(user.publicKey),
+      lamportsBefore - (rent - rentBefore)
+    );
+
+    try {
+      await program.methods
+        .increaseObservationCardinality(OBSERVATION_CAPACITY + 1)
+        .accountsPartial({ payer: user.publicKey, pool })
+        .signers([user])
+        .rpc();
+      assert.fail("cardinality above the capacity was accepted");
+    } catch (err) {
+      assert.include(String(err), "InvalidObservationCardinality");
+    }
+  });
+
+  it("only lets the config admin create pools", async () => {
+    // a pair without a pool, the vault inits run before the admin check
+    const [x, y] = await createMintPair();
+
+    try {
+      await program.methods
+        .initializePool(FEE_TIER_INDEX)
+        .accountsPartial({
+          authority: user.publicKey,
+          mintX: x,
+          mintY: y,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+          pool: poolFor(x, y),
+        })
+        .signers([user])
+        .rpc();
+      assert.fail("a pool was created by a key that is not the admin");
+    } catch (err) {
+      assert.include(String(err), "Unauthorized");
+    }
+  });
+
+  it("rejects unsorted mints", async () => {
+    try {
+      await program.methods
+        .initializePool(FEE_TIER_INDEX)
+        .accountsPartial({
+          authority: authority.publicKey,
+          mintX: mintY,
+          mintY: mintX,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+          pool: poolFor(mintY, mintX),
+        })
+        .rpc();
+      assert.fail("unsorted mints were accepted");
+    } catch (err) {
+      assert.include(String(err), "InvalidMintOrder");
+    }
+  });
+
+  it("rejects a pool over a single mint", async () => {
+    try {
+      await program.methods
+        .initializePool(FEE_TIER_INDEX)
+        .accountsPartial({
+          authority: authority.publicKey,
+          mintX,
+          mintY: mintX,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+          pool: poolFor(mintX, mintX),
+        })
+        .rpc();
+      assert.fail("a pool over a single mint was accepted");
+    } catch (err) {
+      assert.include(String(err), "SameMint");
+    }
+  });
+
+  it("rejects a fee tier index outside the config", async () => {
+    const [x, y] = await createMintPair();
+
+    try {
+      await program.methods
+        .initializePool(FEE_TIERS.length)
+        .accountsPartial({
+          authority: authority.publicKey,
+          mintX: x,
+          mintY: y,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+          pool: poolFor(x, y),
+        })
+        .rpc();
+      assert.fail("a fee tier index outside the config was accepted");
+    } catch (err) {
+      assert.include(String(err), "InvalidFeeTier");
+    }
+  });
+
+  it("accepts a token-2022 mint", async () => {
+    const mint2022 = await createMint(
+      connection,
+      authority,
+      authority.publicKey,
+      null,
+      6,
+      undefined,
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+
+    // pair the token-2022 mint with the classic one, in sorted order
+    const [[x, programX], [y, programY]] = [
+      [mintX, TOKEN_PROGRAM_ID],
+      [mint2022, TOKEN_2022_PROGRAM_ID],
+    ].sort((a, b) => Buffer.compare(a[0].toBuffer(), b[0].toBuffer()));
+    const newPool = poolFor(x, y);
+
+    await program.methods
+      .initializePool(FEE_TIER_INDEX)
+      .accountsPartial({
+        authority: authority.publicKey,
+        mintX: x,
+        mintY: y,
+        tokenProgramX: programX,
+        tokenProgramY: programY,
+        ataX: getAssociatedTokenAddressSync(x, newPool, true, programX),
+        ataY: getAssociatedTokenAddressSync(y, newPool, true, programY),
+        pool: newPool,
+      })
+      .rpc();
+
+    const state = await program.account.pool.fetch(newPool);
+    assert.ok(state.mintX.equals(x));
+    assert.ok(state.mintY.equals(y));
+  });
+
+  it("rejects a mint with a permanent delegate", async () => {
+    const mint = Keypair.generate();
+    const mintLen = getMintLen([ExtensionType.PermanentDelegate]);
+
+    await sendAndConfirmTransaction(
+      connection,
+      new Transaction().add(
+        SystemProgram.createAccount({
+          fromPubkey: authority.publicKey,
+          newAccountPubkey: mint.publicKey,
+          space: mintLen,
+          lamports: await connection.getMinimumBalanceForRentExemption(
+            mintLen
+          ),
+          programId: TOKEN_2022_PROGRAM_ID,
+        }),
+        createInitializePermanentDelegateInstruction(
+          mint.publicKey,
+          authority.publicKey,
+          TOKEN_2022_PROGRAM_ID
+        ),
+        createInitializeMintInstruction(
+          mint.publicKey,
+          6,
+          authority.publicKey,
+          null,
+          TOKEN_2022_PROGRAM_ID
+        )
+      ),
+      [authority, mint]
+    );
+
+    const [[x, programX], [y, programY]] = [
+      [mintX, TOKEN_PROGRAM_ID],
+      [mint.publicKey, TOKEN_2022_PROGRAM_ID],
+    ].sort((a, b) => Buffer.compare(a[0].toBuffer(), b[0].toBuffer()));
+    const newPool = poolFor(x, y);
+
+    try {
+      await program.methods
+        .initializePool(FEE_TIER_INDEX)
+        .accountsPartial({
+          authority: authority.publicKey,
+          mintX: x,
+          mintY: y,
+          tokenProgramX: programX,
+          tokenProgramY: programY,
+          ataX: getAssociatedTokenAddressSync(x, newPool, true, programX),
+          ataY: getAssociatedTokenAddressSync(y, newPool, true, programY),
+          pool: newPool,
+        })
+        .rpc();
+      assert.fail("a mint with a permanent delegate was accepted");
+    } catch (err) {
+      assert.include(String(err), "UnsupportedMintExtension");
+    }
+  });
+
+  it("locks MINIMUM_LIQUIDITY on the first deposit", async () => {
+    await program.methods
+      .provideLiquidity(
+        new BN(1_000_000),
+        new BN(4_000_000),
+        new BN(0),
+        false,
+        null
+      )
+      .accountsPartial({
+        user: user.publicKey,
+        mintX,
+        mintY,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        ataX,
+        ataY,
+        lpTokenMint,
+        pool,
+        userAtaX,
+        userAtaY,
+        userLpAta,
+        lockedLpAta,
+      })
+      .signers([user])
+      .rpc();
+
+    // sqrt(1_000_000 * 4_000_000) = 2_000_000
+    assert.equal(await lpSupply(), 2_000_000);
+    assert.equal(await balance(lockedLpAta), MINIMUM_LIQUIDITY);
+    assert.equal(await balance(userLpAta), 2_000_000 - MINIMUM_LIQUIDITY);
+    assert.equal(await balance(ataX), 1_000_000);
+    assert.equal(await balance(ataY), 4_000_000);
+
+    const state = await program.account.pool.fetch(pool);
+    assert.equal(state.reserveX.toNumber(), 1_000_000);
+    assert.equal(state.reserveY.toNumber(), 4_000_000);
+  });
+
+  it("lets the same wallet deposit again", async () => {
+    const supplyBefore = await lpSupply();
+    const userLpBefore = await balance(userLpAta);
+
+    // same ratio as the pool (1:4), so exactly half of the supply is minted
+    await program.methods
+      .provideLiquidity(
+        new BN(500_000),
+        new BN(2_000_000),
+        new BN(0),
+        false,
+        null
+      )
+      .accountsPartial({
+        user: user.publicKey,
+        mintX,
+        mintY,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        ataX,
+        ataY,
+        lpTokenMint,
+        pool,
+        userAtaX,
+        userAtaY,
+        userLpAta,
+        lockedLpAta,
+      })
+      .signers([user])
+      .rpc();
+
+    assert.equal(await lpSupply(), supplyBefore + supplyBefore / 2);
+    assert.equal(await balance(userLpAta), userLpBefore + supplyBefore / 2);
+    assert.equal(await balance(ataX), 1_500_000);
+    assert.equal(await balance(ataY), 6_000_000);
+  });
+
+  it("lets the same wallet deposit a third time", async () => {
+    const userLpBefore = await balance(userLpAta);
+
+    await program.methods
+      .provideLiquidity(
+        new BN(150_000),
+        new BN(600_000),
+        new BN(1),
+        false,
+        null
+      )
+      .accountsPartial({
+        user: user.publicKey,
+        mintX,
+        mintY,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        ataX,
+        ataY,
+        lpTokenMint,
+        pool,
+        userAtaX,
+        userAtaY,
+        userLpAta,
+        lockedLpAta,
+      })
+      .signers([user])
+      .rpc();
+
+    // 150_000 * 3_000_000 / 1_500_000
+    assert.equal(await balance(userLpAta), userLpBefore + 300_000);
+  });
+
+  it("accumulates the price between updates", async () => {
+    const before = await program.account.pool.fetch(pool);
+
+    // let at least one second pass so the accumulators move
+    await new Promise((resolve) => setTimeout(resolve, 2_000));
+
+    await program.methods
+      .provideLiquidity(
+        new BN(100_000),
+        new BN(400_000),
+        new BN(1),
+        false,
+        null
+      )
+      .accountsPartial({
+        user: user.publicKey,
+        mintX,
+        mintY,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        ataX,
+        ataY,
+        lpTokenMint,
+        pool,
+        userAtaX,
+        userAtaY,
+        userLpAta,
+        lockedLpAta,
+      })
+      .signers([user])
+      .rpc();
+
+    const after = await program.account.pool.fetch(pool);
+    const elapsed =
+      after.lastUpdateTimestamp.toNumber() -
+      before.lastUpdateTimestamp.toNumber();
+    assert.isAbove(elapsed, 0);
+
+    // reserves were 1:4, x was worth 4 y (4 << 64), y a quarter x (1 << 62)
+    assert.ok(
+      after.priceXCumulative
+        .sub(before.priceXCumulative)
+        .eq(new BN(4).shln(64).muln(elapsed))
+    );
+    assert.ok(
+      after.priceYCumulative
+        .sub(before.priceYCumulative)
+        .eq(new BN(1).shln(62).muln(elapsed))
+    );
+  });
+
+  it("observes interpolated cumulative prices", async () => {
+    const state = await program.account.pool.fetch(pool);
+    const written = (await readObservations(observations))
+      .filter((o) => o.timestamp.toNumber() > 0)
+      .sort((a, b) => a.timestamp.toNumber() - b.timestamp.toNumber());
+    assert.isAbove(written.length, 1);
+
+    // the newest observation is the last accumulator update
+    const newest = written[written.length - 1];
+    assert.ok(newest.priceXCumulative.eq(state.priceXCumulative));
+
+    // the price was 1:4 between the two observations before the newest
+    const t2 = newest.timestamp.toNumber();
+    const previous = written[written.length - 2];
+    const t1 = previous.timestamp.toNumber();
+
+    const [current] = await program.methods
+      .observe([0])
+      .accountsPartial({ pool })
+      .view();
+    const oldest = written[0].timestamp.toNumber();
+    const span = Math.min(25, current.timestamp.toNumber() - oldest);
+
+    const secondsAgo = [...Array(span).keys()];
+    const observed = await program.methods
+      .observe(secondsAgo)
+      .accountsPartial({ pool })
+      .view();
+    assert.equal(observed.length, span);
+
+    const between = observed.filter(
+      (o) => o.timestamp.toNumber() >= t1 && o.timestamp.toNumber() <= t2
+    );
+    assert.isAbove(between.length, 0);
+
+    for (const o of between) {
+      const elapsed = o.timestamp.toNumber() - t1;
+      assert.ok(
+        o.priceXCumulative
+          .sub(previous.priceXCumulative)
+          .eq(new BN(4).shln(64).muln(elapsed))
+      );
+    }
+  });
+
+  it("withdraw math matches deposit math", async () => {
+    const supplyBefore = await lpSupply();
+    const vaultXBefore = await balance(ataX);
+    const vaultYBefore = await balance(ataY);
+    const userXBefore = await balance(userAtaX);
+    const userYBefore = await balance(userAtaY);
+
+    const userLp = await balance(userLpAta);
+
+    // same formula as `xy_from_l`, used by the deposit to price lp tokens
+    const expectedX = Math.floor((vaultXBefore * userLp) / supplyBefore);
+    const expectedY = Math.floor((vaultYBefore * userLp) / supplyBefore);
+
+    // one token short on either side is rejected before anything is burned
+    for (const [minX, minY] of [
+      [expectedX + 1, expectedY],
+      [expectedX, expectedY + 1],
+    ]) {
+      try {
+        await program.methods
+          .withdraw(new BN(userLp), new BN(minX), new BN(minY), false, null)
+          .accountsPartial({
+            user: user.publicKey,
+            mintX,
+            mintY,
+            tokenProgramX: TOKEN_PROGRAM_ID,
+            tokenProgramY: TOKEN_PROGRAM_ID,
+            ataX,
+            ataY,
+            lpTokenMint,
+            pool,
+            userAtaX,
+            userAtaY,
+            userLpAta,
+          })
+          .signers([user])
+          .rpc();
+        assert.fail("withdraw ignored its minimums");
+      } catch (err) {
+        assert.include(String(err), "SlippageExceeded");
+      }
+    }
+    assert.equal(await balance(userLpAta), userLp);
+
+    await program.methods
+      .withdraw(
+        new BN(userLp),
+        new BN(expectedX),
+        new BN(expectedY),
+        false,
+        null
+      )
+      .accountsPartial({
+        user: user.publicKey,
+        mintX,
+        mintY,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        ataX,
+        ataY,
+        lpTokenMint,
+        pool,
+        userAtaX,
+        userAtaY,
+        userLpAta,
+      })
+      .signers([user])
+      .rpc();
+
+    assert.equal((await balance(userAtaX)) - userXBefore, expectedX);
+    assert.equal((await balance(userAtaY)) - userYBefore, expectedY);
+
+    // only the locked liquidity is left and it backs the remaining reserves
+    assert.equal(await lpSupply(), MINIMUM_LIQUIDITY);
+    assert.equal(await balance(ataX), vaultXBefore - expectedX);
+    assert.equal(await balance(ataY), vaultYBefore - expectedY);
+
+    const state = await program.account.pool.fetch(pool);
+    assert.equal(state.reserveX.toNumber(), vaultXBefore - expectedX);
+    assert.equal(state.reserveY.toNumber(), vaultYBefore - expectedY);
+  });
+
+  describe("transfer fee mint", () => {
+    // 1% token-2022 transfer fee on one side of the pair
+    const TRANSFER_FEE_BP = 100;
+    const MAX_TRANSFER_FEE = 1_000_000_000;
+
+    let feeMint: PublicKey;
+    let feePool: PublicKey;
+    let feeIsX: boolean;
+    let userFeeAta: PublicKey;
+    let userClassicAta: PublicKey;
+    let accounts: { [name: string]: PublicKey };
+
+    const transferFee = (amount: number) =>
+      Math.min(
+        Math.ceil((amount * TRANSFER_FEE_BP) / 10_000),
+        MAX_TRANSFER_FEE
+      );
+
+    const reserves = async () => {
+      const state = await program.account.pool.fetch(feePool);
+      const [x, y] = [state.reserveX.toNumber(), state.reserveY.toNumber()];
+      return feeIsX ? { fee: x, classic: y } : { fee: y, classic: x };
+    };
+
+    before(async () => {
+      const mint = Keypair.generate();
+      const mintLen = getMintLen([ExtensionType.TransferFeeConfig]);
+
+      await sendAndConfirmTransaction(
+        connection,
+        new Transaction().add(
+          SystemProgram.createAccount({
+            fromPubkey: authority.publicKey,
+            newAccountPubkey: mint.publicKey,
+            space: mintLen,
+            lamports: await connection.getMinimumBalanceForRentExemption(
+              mintLen
+            ),
+            programId: TOKEN_2022_PROGRAM_ID,
+          }),
+          createInitializeTransferFeeConfigInstruction(
+            mint.publicKey,
+            authority.publicKey,
+            authority.publicKey,
+            TRANSFER_FEE_BP,
+            BigInt(MAX_TRANSFER_FEE),
+            TOKEN_2022_PROGRAM_ID
+          ),
+          createInitializeMintInstruction(
+            mint.publicKey,
+            6,
+            authority.publicKey,
+            null,
+            TOKEN_2022_PROGRAM_ID
+          )
+        ),
+        [authority, mint]
+      );
+      feeMint = mint.publicKey;
+
+      userFeeAta = (
+        await getOrCreateAssociatedTokenAccount(
+          connection,
+          user,
+          feeMint,
+          user.publicKey,
+          false,
+          undefined,
+          undefined,
+          TOKEN_2022_PROGRAM_ID
+        )
+      ).address;
+      userClassicAta = userAtaX;
+
+      await mintTo(
+        connection,
+        authority,
+        feeMint,
+        userFeeAta,
+        authority,
+        1_000_000_000,
+        [],
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+
+      // pair the transfer fee mint with the classic mint x, in sorted order
+      feeIsX = Buffer.compare(feeMint.toBuffer(), mintX.toBuffer()) < 0;
+      const [x, y] = feeIsX ? [feeMint, mintX] : [mintX, feeMint];
+      const [programX, programY] = feeIsX
+        ? [TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID]
+        : [TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID];
+
+      feePool = poolFor(x, y);
+      const [feeLpMint] = PublicKey.findProgramAddressSync(
+        [Buffer.from(LP_TOKEN_MINT_SEED), feePool.toBuffer()],
+        program.programId
+      );
+
+      accounts = {
+        mintX: x,
+        mintY: y,
+        tokenProgramX: programX,
+        tokenProgramY: programY,
+        ataX: getAssociatedTokenAddressSync(x, feePool, true, programX),
+        ataY: getAssociatedTokenAddressSync(y, feePool, true, programY),
+        lpTokenMint: feeLpMint,
+        pool: feePool,
+        userAtaX: feeIsX ? userFeeAta : userClassicAta,
+        userAtaY: feeIsX ? userClassicAta : userFeeAta,
+        userLpAta: getAssociatedTokenAddressSync(feeLpMint, user.publicKey),
+        lockedLpAta: getAssociatedTokenAddressSync(feeLpMint, feePool, true),
+      };
+
+      await program.methods
+        .initializePool(FEE_TIER_INDEX)
+        .accountsPartial({ authority: authority.publicKey, ...accounts })
+        .rpc();
+    });
+
+    it("deposits the net amount the vault receives", async () => {
+      await program.methods
+        .provideLiquidity(
+          new BN(1_000_000),
+          new BN(1_000_000),
+          new BN(0),
+          false,
+          null
+        )
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+
+      // the user spends 1_000_000 of each, the fee side vault gets 1% less
+      const { fee, classic } = await reserves();
+      assert.equal(fee, 1_000_000 - transferFee(1_000_000));
+      assert.equal(classic, 1_000_000);
+
+      const feeVault = feeIsX ? accounts.ataX : accounts.ataY;
+      assert.equal(await balance(feeVault), fee);
+    });
+
+    it("prices a swap on the net input", async () => {
+      const before = await reserves();
+      const classicBefore = await balance(userClassicAta);
+
+      const amountIn = 100_000;
+      const netIn = amountIn - transferFee(amountIn);
+      const expectedOut = amountOut(netIn, before.fee, before.classic);
+
+      await program.methods
+        .swap(new BN(amountIn), new BN(expectedOut), feeIsX, false, null)
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+
+      assert.equal(
+        (await balance(userClassicAta)) - classicBefore,
+        expectedOut
+      );
+
+      // protocol fee is charged on the net input and set aside
+      const protocolFee = Math.floor((netIn * 6) / 10_000);
+      const after = await reserves();
+      assert.equal(after.fee, before.fee + netIn - protocolFee);
+      assert.equal(after.classic, before.classic - expectedOut);
+    });
+
+    it("applies amount_out_min to what the user receives", async () => {
+      const before = await reserves();
+      const feeBefore = await balance(userFeeAta);
+
+      const amountIn = 50_000;
+      const out = amountOut(amountIn, before.classic, before.fee);
+      const received = out - transferFee(out);
+
+      try {
+        await program.methods
+          .swap(new BN(amountIn), new BN(received + 1), !feeIsX, false, null)
+          .accountsPartial({ user: user.publicKey, ...accounts })
+          .signers([user])
+          .rpc();
+        assert.fail("swap ignored the output transfer fee");
+      } catch (err) {
+        assert.include(String(err), "SlippageExceeded");
+      }
+
+      await program.methods
+        .swap(new BN(amountIn), new BN(received), !feeIsX, false, null)
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+
+      assert.equal((await balance(userFeeAta)) - feeBefore, received);
+    });
+
+    it("applies withdraw minimums to what the user receives", async () => {
+      const { fee, classic } = await reserves();
+      const supply = Number(
+        (await getMint(connection, accounts.lpTokenMint)).supply
+      );
+      const userLp = await balance(accounts.userLpAta);
+
+      const feeOut = Math.floor((fee * userLp) / supply);
+      const classicOut = Math.floor((classic * userLp) / supply);
+      const feeReceived = feeOut - transferFee(feeOut);
+
+      const feeBefore = await balance(userFeeAta);
+      const classicBefore = await balance(userClassicAta);
+
+      const [minX, minY] = feeIsX
+        ? [feeReceived, classicOut]
+        : [classicOut, feeReceived];
+
+      await program.methods
+        .withdraw(new BN(userLp), new BN(minX), new BN(minY), false, null)
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+
+      assert.equal((await balance(userFeeAta)) - feeBefore, feeReceived);
+      assert.equal(
+        (await balance(userClassicAta)) - classicBefore,
+        classicOut
+      );
+    });
+  });
+
+  describe("native sol", () => {
+    let solIsX: boolean;
+    let userWsolAta: PublicKey;
+    let accounts: { [name: string]: PublicKey };
+
+    before(async () => {
+      // pair wsol with the classic mint y, the user has no wsol account yet
+      solIsX = Buffer.compare(NATIVE_MINT.toBuffer(), mintY.toBuffer()) < 0;
+      const [x, y] = solIsX ? [NATIVE_MINT, mintY] : [mintY, NATIVE_MINT];
+
+      const solPool = poolFor(x, y);
+      const [solLpMint] = PublicKey.findProgramAddressSync(
+        [Buffer.from(LP_TOKEN_MINT_SEED), solPool.toBuffer()],
+        program.programId
+      );
+      userWsolAta = getAssociatedTokenAddressSync(NATIVE_MINT, user.publicKey);
+
+      accounts = {
+        mintX: x,
+        mintY: y,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        ataX: getAssociatedTokenAddressSync(x, solPool, true),
+        ataY: getAssociatedTokenAddressSync(y, solPool, true),
+        lpTokenMint: solLpMint,
+        pool: solPool,
+        userAtaX: solIsX ? userWsolAta : userAtaY,
+        userAtaY: solIsX ? userAtaY : userWsolAta,
+        userLpAta: getAssociatedTokenAddressSync(solLpMint, user.publicKey),
+        lockedLpAta: getAssociatedTokenAddressSync(solLpMint, solPool, true),
+      };
+
+      await program.methods
+        .initializePool(FEE_TIER_INDEX)
+        .accountsPartial({ authority: authority.publicKey, ...accounts })
+        .rpc();
+    });
+
+    it("wraps native sol on deposit", async () => {
+      const [maxX, maxY] = solIsX
+        ? [1_000_000_000, 4_000_000]
+        : [4_000_000, 1_000_000_000];
+
+      await program.methods
+        .provideLiquidity(new BN(maxX), new BN(maxY), new BN(0), true, null)
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+
+      const solVault = solIsX ? accounts.ataX : accounts.ataY;
+      assert.equal(await balance(solVault), 1_000_000_000);
+      // exactly the deposit was wrapped, the wsol account is left empty
+      assert.equal(await balance(userWsolAta), 0);
+    });
+
+    it("unwraps native sol on the output side", async () => {
+      const lamportsBefore = await connection.getBalance(user.publicKey);
+
+      await program.methods
+        .swap(new BN(100_000), new BN(1), !solIsX, true, null)
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+
+      // the wsol account was closed after the output landed in it
+      assert.isNull(await connection.getAccountInfo(userWsolAta));
+      assert.isAbove(
+        await connection.getBalance(user.publicKey),
+        lamportsBefore
+      );
+    });
+
+    it("leaves wsol alone without wrap_unwrap_sol", async () => {
+      await program.methods
+        .swap(new BN(100_000), new BN(1), !solIsX, false, null)
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+
+      // the output stays wrapped for whatever the caller does next
+      assert.isAbove(await balance(userWsolAta), 0);
+    });
+
+    it("wraps native sol on the input side of a swap", async () => {
+      const yBefore = await balance(userAtaY);
+
+      // more than the wsol account holds, the shortfall is wrapped
+      await program.methods
+        .swap(new BN(100_000_000), new BN(1), solIsX, true, null)
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+
+      assert.isAbove(await balance(userAtaY), yBefore);
+      assert.equal(await balance(userWsolAta), 0);
+    });
+  });
+
+  describe("swaps", () => {
+    let accounts: { [name: string]: PublicKey };
+
+    before(async () => {
+      accounts = await createPool(1_000_000_000);
+
+      await program.methods
+        .provideLiquidity(
+          new BN(1_000_000),
+          new BN(4_000_000),
+          new BN(0),
+          false,
+          null
+        )
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+    });
+
+    it("pays the swap out in the other token", async () => {
+      const [reserveX, reserveY] = await reservesOf(accounts.pool);
+      const xBefore = await balance(accounts.userAtaX);
+      const yBefore = await balance(accounts.userAtaY);
+      const expectedOut = amountOut(100_000, reserveX, reserveY);
+
+      await program.methods
+        .swap(new BN(100_000), new BN(expectedOut), true, false, null)
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+
+      // x goes in, y comes out
+      assert.equal(xBefore - (await balance(accounts.userAtaX)), 100_000);
+      assert.equal((await balance(accounts.userAtaY)) - yBefore, expectedOut);
+    });
+
+    it("swaps for an exact output", async () => {
+      const [reserveX, reserveY] = await reservesOf(accounts.pool);
+      const xBefore = await balance(accounts.userAtaX);
+      const yBefore = await balance(accounts.userAtaY);
+
+      // y in, exactly 50_000 x out
+      const expectedIn = amountIn(50_000, reserveY, reserveX);
+
+      try {
+        await program.methods
+          .swapExactOut(
+            new BN(50_000),
+            new BN(expectedIn - 1),
+            false,
+            false,
+            null
+          )
+          .accountsPartial({ user: user.publicKey, ...accounts })
+          .signers([user])
+          .rpc();
+        assert.fail("swap_exact_out ignored amount_in_max");
+      } catch (err) {
+        assert.include(String(err), "SlippageExceeded");
+      }
+
+      await program.methods
+        .swapExactOut(new BN(50_000), new BN(expectedIn), false, false, null)
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+
+      assert.equal((await balance(accounts.userAtaX)) - xBefore, 50_000);
+      assert.equal(yBefore - (await balance(accounts.userAtaY)), expectedIn);
+
+      // rounding stays in the pool, k never goes down
+      const [newX, newY] = await reservesOf(accounts.pool);
+      assert.equal(newX, reserveX - 50_000);
+      assert.isTrue(
+        BigInt(newX) * BigInt(newY) >= BigInt(reserveX) * BigInt(reserveY)
+      );
+    });
+
+    it("collects the protocol fees to the treasury", async () => {
+      const treasuryAtaX = (
+        await getOrCreateAssociatedTokenAccount(
+          connection,
+          authority,
+          accounts.mintX,
+          authority.publicKey
+        )
+      ).address;
+      const treasuryAtaY = (
+        await getOrCreateAssociatedTokenAccount(
+          connection,
+          authority,
+          accounts.mintY,
+          authority.publicKey
+        )
+      ).address;
+
+      const before = await program.account.pool.fetch(accounts.pool);
+      const feeX = before.feeCollectedX.toNumber();
+      const feeY = before.feeCollectedY.toNumber();
+      assert.isAbove(feeX, 0);
+      assert.isAbove(feeY, 0);
+
+      try {
+        await program.methods
+          .collectProtocolFees()
+          .accountsPartial({
+            authority: user.publicKey,
+            ...accounts,
+            treasuryAtaX,
+            treasuryAtaY,
+          })
+          .signers([user])
+          .rpc();
+        assert.fail("a key that is not the authority collected the fees");
+      } catch (err) {
+        assert.include(String(err), "ConstraintHasOne");
+      }
+
+      // the treasury token accounts have to belong to the config treasury
+      try {
+        await program.methods
+          .collectProtocolFees()
+          .accountsPartial({
+            authority: authority.publicKey,
+            ...accounts,
+            treasuryAtaX: accounts.userAtaX,
+            treasuryAtaY: accounts.userAtaY,
+          })
+          .rpc();
+        assert.fail("fees were sent to accounts outside the treasury");
+      } catch (err) {
+        assert.include(String(err), "ConstraintTokenOwner");
+      }
+
+      const treasuryXBefore = await balance(treasuryAtaX);
+      const treasuryYBefore = await balance(treasuryAtaY);
+
+      await program.methods
+        .collectProtocolFees()
+        .accountsPartial({
+          authority: authority.publicKey,
+          ...accounts,
+          treasuryAtaX,
+          treasuryAtaY,
+        })
+        .rpc();
+
+      assert.equal((await balance(treasuryAtaX)) - treasuryXBefore, feeX);
+      assert.equal((await balance(treasuryAtaY)) - treasuryYBefore, feeY);
+
+      // counters are reset and the vaults hold exactly the reserves again
+      const after = await program.account.pool.fetch(accounts.pool);
+      assert.equal(after.feeCollectedX.toNumber(), 0);
+      assert.equal(after.feeCollectedY.toNumber(), 0);
+      assert.equal(await balance(accounts.ataX), after.reserveX.toNumber());
+      assert.equal(await balance(accounts.ataY), after.reserveY.toNumber());
+    });
+
+    it("rejects a swap past its deadline", async () => {
+      const now = await connection.getBlockTime(await connection.getSlot());
+
+      try {
+        await program.methods
+          .swap(new BN(10_000), new BN(1), true, false, new BN(now - 60))
+          .accountsPartial({ user: user.publicKey, ...accounts })
+          .signers([user])
+          .rpc();
+        assert.fail("an expired swap went through");
+      } catch (err) {
+        assert.include(String(err), "Expired");
+      }
+
+      const yBefore = await balance(accounts.userAtaY);
+
+      await program.methods
+        .swap(new BN(10_000), new BN(1), true, false, new BN(now + 60))
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+
+      assert.isAbove(await balance(accounts.userAtaY), yBefore);
+    });
+  });
+
+  describe("single sided liquidity", () => {
+    it("rejects a single sided deposit into an empty pool", async () => {
+      const accounts = await createPool(1_000_000);
+
+      try {
+        await program.methods
+          .provideLiquiditySingle(new BN(100_000), true, new BN(0), false, null)
+          .accountsPartial({ user: user.publicKey, ...accounts })
+          .signers([user])
+          .rpc();
+        assert.fail("a single sided deposit went into an empty pool");
+      } catch (err) {
+        assert.include(String(err), "ZeroReserves");
+      }
+    });
+
+    it("prices a single sided deposit into large reserves", async () => {
+      const reserve = new BN("5000000000000000");
+      const accounts = await createPool(6_000_000_000_000_000);
+
+      await program.methods
+        .provideLiquidity(reserve, reserve, new BN(0), false, null)
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+
+      const xBefore = await balance(accounts.userAtaX);
+      const lpBefore = await balance(accounts.userLpAta);
+
+      // R * inner overflows u128 here, the optimal swap is 50_075_112 of the
+      // 100_000_000 and the rest matches the pool ratio after it
+      const expectedLp = 49_924_885;
+
+      await program.methods
+        .provideLiquiditySingle(
+          new BN(100_000_000),
+          true,
+          new BN(expectedLp),
+          false,
+          null
+        )
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+
+      assert.equal(
+        (await balance(accounts.userLpAta)) - lpBefore,
+        expectedLp
+      );
+      assert.isAtMost(
+        xBefore - (await balance(accounts.userAtaX)),
+        100_000_000
+      );
+    });
+
+    it("withdraws into a single token", async () => {
+      const accounts = await createPool(1_000_000_000);
+
+      await program.methods
+        .provideLiquidity(
+          new BN(1_000_000),
+          new BN(4_000_000),
+          new BN(0),
+          false,
+          null
+        )
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+
+      // more than the lp supply would take out more than the reserves
+      try {
+        await program.methods
+          .withdrawSingle(new BN(2_000_001), true, new BN(0), false, null)
+          .accountsPartial({ user: user.publicKey, ...accounts })
+          .signers([user])
+          .rpc();
+        assert.fail("withdrew more than the lp supply");
+      } catch (err) {
+        assert.include(String(err), "OutputExceedsReserves");
+      }
+
+      const xBefore = await balance(accounts.userAtaX);
+      const yBefore = await balance(accounts.userAtaY);
+
+      // half the supply is 500_000 x and 2_000_000 y, the y is swapped into
+      // the 500_000 x and 2_000_000 y that stay in the pool
+      const expectedOut = 500_000 + amountOut(2_000_000, 2_000_000, 500_000);
+
+      await program.methods
+        .withdrawSingle(
+          new BN(1_000_000),
+          true,
+          new BN(expectedOut),
+          false,
+          null
+        )
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+
+      assert.equal((await balance(accounts.userAtaX)) - xBefore, expectedOut);
+      assert.equal(await balance(accounts.userAtaY), yBefore);
+    });
+  });
+
+  describe("skim and sync", () => {
+    let accounts: { [name: string]: PublicKey };
+
+    before(async () => {
+      accounts = await createPool(1_000_000_000);
+
+      await program.methods
+        .provideLiquidity(
+          new BN(1_000_000),
+          new BN(4_000_000),
+          new BN(0),
+          false,
+          null
+        )
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+    });
+
+    it("skims donations on top of the reserves", async () => {
+      // a plain transfer into the vault is not part of the reserves
+      await transfer(
+        connection,
+        user,
+        accounts.userAtaX,
+        accounts.ataX,
+        user,
+        10_000
+      );
+
+      try {
+        await program.methods
+          .skim()
+          .accountsPartial({
+            ...accounts,
+            recipientAtaX: accounts.userAtaY,
+            recipientAtaY: accounts.userAtaY,
+          })
+          .rpc();
+        assert.fail("skim sent x to a y token account");
+      } catch (err) {
+        assert.include(String(err), "ConstraintTokenMint");
+      }
+
+      const xBefore = await balance(accounts.userAtaX);
+
+      await program.methods
+        .skim()
+        .accountsPartial({
+          ...accounts,
+          recipientAtaX: accounts.userAtaX,
+          recipientAtaY: accounts.userAtaY,
+        })
+        .rpc();
+
+      assert.equal((await balance(accounts.userAtaX)) - xBefore, 10_000);
+      assert.equal(await balance(accounts.ataX), 1_000_000);
+    });
+
+    it("syncs donations into the reserves", async () => {
+      await transfer(
+        connection,
+        user,
+        accounts.userAtaY,
+        accounts.ataY,
+        user,
+        10_000
+      );
+
+      // only the pool authority can sync
+      try {
+        await program.methods
+          .sync()
+          .accountsPartial({ authority: user.publicKey, ...accounts })
+          .signers([user])
+          .rpc();
+        assert.fail("sync accepted a signer that is not the authority");
+      } catch (err) {
+        assert.include(String(err), "ConstraintHasOne");
+      }
+
+      await program.methods
+        .sync()
+        .accountsPartial({ authority: authority.publicKey, ...accounts })
+        .rpc();
+
+      const [reserveX, reserveY] = await reservesOf(accounts.pool);
+      assert.equal(reserveX, 1_000_000);
+      assert.equal(reserveY, 4_010_000);
+    });
+
+    it("pauses sync and fee collection but not withdrawals", async () => {
+      const treasuryAtaX = (
+        await getOrCreateAssociatedTokenAccount(
+          connection,
+          authority,
+          accounts.mintX,
+          authority.publicKey
+        )
+      ).address;
+      const treasuryAtaY = (
+        await getOrCreateAssociatedTokenAccount(
+          connection,
+          authority,
+          accounts.mintY,
+          authority.publicKey
+        )
+      ).address;
+
+      await program.methods
+        .setPaused(true)
+        .accountsPartial({ admin: authority.publicKey })
+        .rpc();
+
+      try {
+        await program.methods
+          .sync()
+          .accountsPartial({ authority: authority.publicKey, ...accounts })
+          .rpc();
+        assert.fail("sync ran while paused");
+      } catch (err) {
+        assert.include(String(err), "AmmPaused");
+      }
+
+      try {
+        await program.methods
+          .collectProtocolFees()
+          .accountsPartial({
+            authority: authority.publicKey,
+            ...accounts,
+            treasuryAtaX,
+            treasuryAtaY,
+          })
+          .rpc();
+        assert.fail("protocol fees were collected while paused");
+      } catch (err) {
+        assert.include(String(err), "AmmPaused");
+      }
+
+      // LPs can always exit
+      const lpBefore = await balance(accounts.userLpAta);
+      await program.methods
+        .withdraw(new BN(1_000), new BN(0), new BN(0), false, null)
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+      assert.equal(await balance(accounts.userLpAta), lpBefore - 1_000);
+
+      await program.methods
+        .setPaused(false)
+        .accountsPartial({ admin: authority.publicKey })
+        .rpc();
+    });
+  });
+
+  describe("pool admin", () => {
+    let accounts: { [name: string]: PublicKey };
+
+    before(async () => {
+      accounts = await createPool(1_000_000_000);
+    });
+
+    after(async () => {
+      // later pools expect the original protocol share
+      await program.methods
+        .updateConfig(FEE_TIERS, PROTOCOL_FEE_SHARE_BP, authority.publicKey)
+        .accountsPartial({ admin: authority.publicKey })
+        .rpc();
+    });
+
+    it("re-applies the config protocol share to the fee tier", async () => {
+      await program.methods
+        .updateConfig(FEE_TIERS, 5_000, authority.publicKey)
+        .accountsPartial({ admin: authority.publicKey })
+        .rpc();
+
+      try {
+        await program.methods
+          .updateFee()
+          .accountsPartial({ authority: user.publicKey, pool: accounts.pool })
+          .signers([user])
+          .rpc();
+        assert.fail("update_fee accepted a signer that is not the authority");
+      } catch (err) {
+        assert.include(String(err), "ConstraintHasOne");
+      }
+
+      const signature = await program.methods
+        .updateFee()
+        .accountsPartial({
+          authority: authority.publicKey,
+          pool: accounts.pool,
+        })
+        .rpc({ commitment: "confirmed" });
+
+      // half of the 30 bp tier now goes to the protocol
+      const state = await program.account.pool.fetch(accounts.pool);
+      assert.equal(state.feeTier, FEE_TIER);
+      assert.equal(state.lpFeeBp, 15);
+      assert.equal(state.protocolFeeBp, 15);
+
+      const tx = await connection.getTransaction(signature, {
+        commitment: "confirmed",
+        maxSupportedTransactionVersion: 0,
+      });
+      const parser = new anchor.EventParser(program.programId, program.coder);
+      const events = [...parser.parseLogs(tx.meta.logMessages)];
+      assert.equal(events.length, 1);
+      assert.equal(events[0].name, "feeUpdated");
+      assert.equal(events[0].data.oldLpFeeBp, 24);
+      assert.equal(events[0].data.oldProtocolFeeBp, 6);
+      assert.equal(events[0].data.newLpFeeBp, 15);
+      assert.equal(events[0].data.newProtocolFeeBp, 15);
+    });
+
+    it("sets the pool fees within MAX_FEE_BP", async () => {
+      try {
+        await program.methods
+          .setFee(MAX_FEE_BP, 1)
+          .accountsPartial({
+            authority: authority.publicKey,
+            pool: accounts.pool,
+          })
+          .rpc();
+        assert.fail("fees above MAX_FEE_BP were accepted");
+      } catch (err) {
+        assert.include(String(err), "InvalidFee");
+      }
+
+      try {
+        await program.methods
+          .setFee(40, 10)
+          .accountsPartial({ authority: user.publicKey, pool: accounts.pool })
+          .signers([user])
+          .rpc();
+        assert.fail("set_fee accepted a signer that is not the authority");
+      } catch (err) {
+        assert.include(String(err), "ConstraintHasOne");
+      }
+
+      const signature = await program.methods
+        .setFee(40, 10)
+        .accountsPartial({
+          authority: authority.publicKey,
+          pool: accounts.pool,
+        })
+        .rpc({ commitment: "confirmed" });
+
+      const state = await program.account.pool.fetch(accounts.pool);
+      assert.equal(state.lpFeeBp, 40);
+      assert.equal(state.protocolFeeBp, 10);
+
+      const tx = await connection.getTransaction(signature, {
+        commitment: "confirmed",
+        maxSupportedTransactionVersion: 0,
+      });
+      const parser = new anchor.EventParser(program.programId, program.coder);
+      const events = [...parser.parseLogs(tx.meta.logMessages)];
+      assert.equal(events.length, 1);
+      assert.equal(events[0].name, "feeUpdated");
+      assert.equal(events[0].data.oldLpFeeBp, 15);
+      assert.equal(events[0].data.oldProtocolFeeBp, 15);
+      assert.equal(events[0].data.newLpFeeBp, 40);
+      assert.equal(events[0].data.newProtocolFeeBp, 10);
+    });
+
+    it("caps fee tiers at MAX_FEE_BP", async () => {
+      try {
+        await program.methods
+          .updateConfig(
+            [...FEE_TIERS, MAX_FEE_BP + 1],
+            PROTOCOL_FEE_SHARE_BP,
+            authority.publicKey
+          )
+          .accountsPartial({ admin: authority.publicKey })
+          .rpc();
+        assert.fail("a fee tier above MAX_FEE_BP was accepted");
+      } catch (err) {
+        assert.include(String(err), "InvalidFee");
+      }
+
+      await program.methods
+        .updateConfig(
+          [...FEE_TIERS, MAX_FEE_BP],
+          PROTOCOL_FEE_SHARE_BP,
+          authority.publicKey
+        )
+        .accountsPartial({ admin: authority.publicKey })
+        .rpc();
+
+      const [config] = PublicKey.findProgramAddressSync(
+        [Buffer.from(AMM_CONFIG_SEED)],
+        program.programId
+      );
+      const state = await program.account.ammConfig.fetch(config);
+      assert.deepEqual(state.feeTiers, [...FEE_TIERS, MAX_FEE_BP]);
+    });
+
+    it("transfers the pool authority in two steps", async () => {
+      try {
+        await program.methods
+          .proposeAuthority(user.publicKey)
+          .accountsPartial({ authority: user.publicKey, pool: accounts.pool })
+          .signers([user])
+          .rpc();
+        assert.fail("a key that is not the authority proposed a new one");
+      } catch (err) {
+        assert.include(String(err), "ConstraintHasOne");
+      }
+
+      await program.methods
+        .proposeAuthority(user.publicKey)
+        .accountsPartial({
+          authority: authority.publicKey,
+          pool: accounts.pool,
+        })
+        .rpc();
+
+      // only the proposed key can accept
+      try {
+        await program.methods
+          .acceptAuthority()
+          .accountsPartial({
+            newAuthority: authority.publicKey,
+            pool: accounts.pool,
+          })
+          .rpc();
+        assert.fail("a key that was not proposed accepted the authority");
+      } catch (err) {
+        assert.include(String(err), "InvalidPendingAuthority");
+      }
+
+      await program.methods
+        .acceptAuthority()
+        .accountsPartial({ newAuthority: user.publicKey, pool: accounts.pool })
+        .signers([user])
+        .rpc();
+
+      const state = await program.account.pool.fetch(accounts.pool);
+      assert.ok(state.authority.equals(user.publicKey));
+      assert.isNull(state.pendingAuthority);
+
+      // the pool address and its signer seeds do not depend on the authority
+      await program.methods
+        .provideLiquidity(
+          new BN(1_000_000),
+          new BN(4_000_000),
+          new BN(0),
+          false,
+          null
+        )
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+      await program.methods
+        .withdraw(new BN(1_000), new BN(1), new BN(1), false, null)
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+
+      await program.methods
+        .lockPool()
+        .accountsPartial({ authority: user.publicKey, pool: accounts.pool })
+        .signers([user])
+        .rpc();
+      assert.isTrue((await program.account.pool.fetch(accounts.pool)).locked);
+    });
+  });
+
+  describe("lock policy", () => {
+    let accounts: { [name: string]: PublicKey };
+
+    before(async () => {
+      accounts = await createPool(1_000_000_000);
+
+      await program.methods
+        .provideLiquidity(
+          new BN(1_000_000),
+          new BN(4_000_000),
+          new BN(0),
+          false,
+          null
+        )
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+
+      await program.methods
+        .lockPool()
+        .accountsPartial({
+          authority: authority.publicKey,
+          pool: accounts.pool,
+        })
+        .rpc();
+    });
+
+    it("blocks swaps and deposits while locked", async () => {
+      const calls = {
+        swap: program.methods.swap(
+          new BN(10_000),
+          new BN(0),
+          true,
+          false,
+          null
+        ),
+        swapExactOut: program.methods.swapExactOut(
+          new BN(10_000),
+          new BN(1_000_000),
+          true,
+          false,
+          null
+        ),
+        provideLiquidity: program.methods.provideLiquidity(
+          new BN(10_000),
+          new BN(40_000),
+          new BN(0),
+          false,
+          null
+        ),
+        provideLiquiditySingle: program.methods.provideLiquiditySingle(
+          new BN(10_000),
+          true,
+          new BN(0),
+          false,
+          null
+        ),
+        withdrawSingle: program.methods.withdrawSingle(
+          new BN(1_000),
+          true,
+          new BN(0),
+          false,
+          null
+        ),
+      };
+
+      for (const [name, call] of Object.entries(calls)) {
+        try {
+          await call
+            .accountsPartial({ user: user.publicKey, ...accounts })
+            .signers([user])
+            .rpc();
+          assert.fail(`${name} went through on a locked pool`);
+        } catch (err) {
+          assert.include(String(err), "PoolIsLocked");
+        }
+      }
+    });
+
+    it("keeps withdrawals open while locked", async () => {
+      const lpBefore = await balance(accounts.userLpAta);
+      const xBefore = await balance(accounts.userAtaX);
+      const yBefore = await balance(accounts.userAtaY);
+
+      await program.methods
+        .withdraw(new BN(2_000), new BN(1), new BN(1), false, null)
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+
+      assert.equal(await balance(accounts.userLpAta), lpBefore - 2_000);
+      assert.isAbove(await balance(accounts.userAtaX), xBefore);
+      assert.isAbove(await balance(accounts.userAtaY), yBefore);
+    });
+
+    it("trades again once unlocked", async () => {
+      try {
+        await program.methods
+          .unlockPool()
+          .accountsPartial({ authority: user.publicKey, pool: accounts.pool })
+          .signers([user])
+          .rpc();
+        assert.fail("unlock_pool accepted a signer that is not the authority");
+      } catch (err) {
+        assert.include(String(err), "ConstraintHasOne");
+      }
+
+      await program.methods
+        .unlockPool()
+        .accountsPartial({
+          authority: authority.publicKey,
+          pool: accounts.pool,
+        })
+        .rpc();
+      assert.isFalse((await program.account.pool.fetch(accounts.pool)).locked);
+
+      const yBefore = await balance(accounts.userAtaY);
+
+      await program.methods
+        .swap(new BN(10_000), new BN(1), true, false, null)
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+
+      assert.isAbove(await balance(accounts.userAtaY), yBefore);
+    });
+  });
+
+  describe("deposit guards", () => {
+    let accounts: { [name: string]: PublicKey };
+
+    before(async () => {
+      accounts = await createPool(1_000_000_000);
+
+      // 2_000_000 lp for 1_000_000 x and 4_000_000 y
+      await program.methods
+        .provideLiquidity(
+          new BN(1_000_000),
+          new BN(4_000_000),
+          new BN(0),
+          false,
+          null
+        )
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+    });
+
+    it("enforces min_lp_out", async () => {
+      const lpBefore = await balance(accounts.userLpAta);
+
+      // a tenth of the pool mints 200_000 lp
+      try {
+        await program.methods
+          .provideLiquidity(
+            new BN(100_000),
+            new BN(400_000),
+            new BN(200_001),
+            false,
+            null
+          )
+          .accountsPartial({ user: user.publicKey, ...accounts })
+          .signers([user])
+          .rpc();
+        assert.fail("deposit minted less than min_lp_out");
+      } catch (err) {
+        assert.include(String(err), "SlippageExceeded");
+      }
+
+      await program.methods
+        .provideLiquidity(
+          new BN(100_000),
+          new BN(400_000),
+          new BN(200_000),
+          false,
+          null
+        )
+        .accountsPartial({ user: user.publicKey, ...accounts })
+        .signers([user])
+        .rpc();
+
+      assert.equal((await balance(accounts.userLpAta)) - lpBefore, 200_000);
+    });
+
+    it("never mints zero lp", async () => {
+      // 1 y is worth half an lp token, rounded down to 0
+      try {
+        await program.methods
+          .provideLiquidity(new BN(1), new BN(1), new BN(0), false, null)
+          .accountsPartial({ user: user.publicKey, ...accounts })
+          .signers([user])
+          .rpc();
+        assert.fail("a deposit minted zero lp");
+      } catch (err) {
+        assert.include(String(err), "LpTokenAmountCannotBeZero");
+      }
+    });
+
+    it("rejects a first deposit below MINIMUM_LIQUIDITY", async () => {
+      const fresh = await createPool(1_000_000_000);
+
+      // sqrt(1_000 * 1_000) would all be locked, nothing left for the user
+      try {
+        await program.methods
+          .provideLiquidity(
+            new BN(1_000),
+            new BN(1_000),
+            new BN(0),
+            false,
+            null
+          )
+          .accountsPartial({ user: user.publicKey, ...fresh })
+          .signers([user])
+          .rpc();
+        assert.fail("a first deposit below MINIMUM_LIQUIDITY was accepted");
+      } catch (err) {
+        assert.include(String(err), "InsufficientInitialLiquidity");
+      }
+
+      await program.methods
+        .provideLiquidity(
+          new BN(1_001),
+          new BN(1_001),
+          new BN(0),
+          false,
+          null
+        )
+        .accountsPartial({ user: user.publicKey, ...fresh })
+        .signers([user])
+        .rpc();
 
-  it("Is initialized!", async () => {
-    // Add your test here.
-    const tx = await program.methods.initialize().rpc();
-    console.log("Your transaction signature", tx);
+      assert.equal(await balance(fresh.lockedLpAta), MINIMUM_LIQUIDITY);
+      assert.equal(await balance(fresh.userLpAta), 1);
+    });
   });
 });